@@ -1,4 +1,8 @@
 use anchor_lang::prelude::*;
+use anchor_lang::AccountsClose;
+use users::verify_deactivated_profile;
+use sponsorship::program::Sponsorship;
+use sponsorship::create_sponsored_account;
 
 declare_id!("8MnP9XdAKdCvyK6KjRgu9AAGRAb7GmNxSDakqt5z8jTM");
 
@@ -10,6 +14,14 @@ const PUBKEY_PAYER_LENGTH: usize = 32;
 const STRING_LENGTH_PREFIX: usize = 4;
 const STRING_LENGTH_FROM_ENCRYPTED_KEY: usize = 128;
 const STRING_LENGTH_TO_ENCRYPTED_KEY: usize = 128;
+const FRIEND_INDEX_PDA_SEED: &[u8] = b"friendindex";
+const FRIEND_PAGE_PDA_SEED: &[u8] = b"friendpage";
+const INDEXED_FRIENDSHIP_PDA_SEED: &[u8] = b"indexed";
+const FRIEND_PAGE_CAPACITY: usize = 32;
+const PUBKEY_USER_LENGTH: usize = 32;
+const PUBKEY_FRIEND_LENGTH: usize = 32;
+const U32_LENGTH: usize = 4;
+const VEC_LENGTH_PREFIX: usize = 4;
 
 
 #[derive(Clone, Copy, AnchorSerialize, AnchorDeserialize, PartialEq)]
@@ -27,29 +39,61 @@ pub mod friends {
     use super::*;
 
     pub fn make_request(ctx: Context<MakeRequest>, user1: Pubkey, user2: Pubkey, k: String) -> Result<()> {
-        let user = ctx.accounts.user.key();
         let request = &mut ctx.accounts.request;
-
         if request.status == Status::Uninitilized {
             request.payer = ctx.accounts.payer.key();
         }
-        if user == user1 {
-            request.from = user1;
-            request.to = user2;
+        open_request(request, ctx.accounts.user.key(), user1, user2, k);
+        Ok(())
+    }
+
+    // a new request is funded by the sponsor's vault, which also gets the rent back when it closes
+    pub fn make_request_sponsored(ctx: Context<MakeRequestSponsored>, user1: Pubkey, user2: Pubkey, k: String) -> Result<()> {
+        let request_info = ctx.accounts.request.to_account_info();
+        let mut request: Account<FriendRequest> = if request_info.owner == &crate::ID {
+            Account::try_from(&request_info)?
         } else {
-            request.from = user2;
-            request.to = user1;
+            let accounts = sponsorship::cpi::accounts::FundRent {
+                sponsor: ctx.accounts.sponsor.to_account_info(),
+                vault: ctx.accounts.vault.to_account_info(),
+                quota: ctx.accounts.quota.to_account_info(),
+                user: ctx.accounts.user.to_account_info(),
+                account: request_info.clone(),
+                instructions: ctx.accounts.instructions.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            };
+            create_sponsored_account(&ctx.accounts.sponsorship_program, accounts, &[&user1.to_bytes()[..32], &user2.to_bytes()[..32]], FriendRequest::LEN, &crate::ID)?;
+            Account::try_from_unchecked(&request_info)?
+        };
+        if request.status == Status::Accepted || request.status == Status::Pending {
+            return Err(error!(ErrorCode::ExistentRequest))
         }
-        request.from_encrypted_key = k;
-        request.to_encrypted_key = "".to_string();
-        request.status = Status::Pending;
-        Ok(())
+        if request.status == Status::Uninitilized {
+            request.payer = ctx.accounts.vault.key();
+        }
+        open_request(&mut request, ctx.accounts.user.key(), user1, user2, k);
+        request.exit(&crate::ID)
     }
 
     pub fn accept_request(ctx: Context<AcceptRequest>, k: String) -> Result<()> {
         let request = &mut ctx.accounts.request;
         request.to_encrypted_key = k;
         request.status = Status::Accepted;
+        ctx.accounts.indexed.payer = ctx.accounts.payer.key();
+
+        // both sides of the friendship get the other appended to the last page of their index
+        add_to_index(&mut ctx.accounts.from_index, &mut ctx.accounts.from_page, request.from, request.to, ctx.accounts.payer.key())?;
+        add_to_index(&mut ctx.accounts.to_index, &mut ctx.accounts.to_page, request.to, request.from, ctx.accounts.payer.key())?;
+        Ok(())
+    }
+
+    // friendships accepted before the index existed are indexed by anyone, once
+    pub fn index_friendship(ctx: Context<IndexFriendship>) -> Result<()> {
+        let request = &ctx.accounts.request;
+        ctx.accounts.indexed.payer = ctx.accounts.payer.key();
+
+        add_to_index(&mut ctx.accounts.from_index, &mut ctx.accounts.from_page, request.from, request.to, ctx.accounts.payer.key())?;
+        add_to_index(&mut ctx.accounts.to_index, &mut ctx.accounts.to_page, request.to, request.from, ctx.accounts.payer.key())?;
         Ok(())
     }
 
@@ -69,6 +113,12 @@ pub mod friends {
         Ok(())
     }
 
+    pub fn transfer_payer(ctx: Context<TransferPayer>) -> Result<()> {
+        let request = &mut ctx.accounts.request;
+        request.payer = ctx.accounts.new_payer.key();
+        Ok(())
+    }
+
     pub fn close_request(_ctx: Context<CloseRequest>) -> Result<()> {
         Ok(())
     }
@@ -78,8 +128,41 @@ pub mod friends {
         request.status = Status::RemovedFriend;
         request.from_encrypted_key = "".to_string();
         request.to_encrypted_key = "".to_string();
+        close_indexed(&ctx.accounts.indexed, &ctx.accounts.indexed_payer)?;
+
+        // the removed entry is replaced by the last entry of the index (swap-remove)
+        remove_from_index(&mut ctx.accounts.from_index, &mut ctx.accounts.from_page, &mut ctx.accounts.from_last_page, request.to)?;
+        remove_from_index(&mut ctx.accounts.to_index, &mut ctx.accounts.to_page, &mut ctx.accounts.to_last_page, request.from)?;
         Ok(())
     }
+
+    // permissionless once one side of the friendship deactivated or closed its profile
+    pub fn remove_deactivated_friend(ctx: Context<RemoveDeactivatedFriend>) -> Result<()> {
+        deactivated_party_check(&ctx.accounts.user, &ctx.accounts.closed_profile, &ctx.accounts.request)?;
+        let request = &mut ctx.accounts.request;
+        request.status = Status::RemovedFriend;
+        request.from_encrypted_key = "".to_string();
+        request.to_encrypted_key = "".to_string();
+        close_indexed(&ctx.accounts.indexed, &ctx.accounts.indexed_payer)?;
+
+        remove_from_index(&mut ctx.accounts.from_index, &mut ctx.accounts.from_page, &mut ctx.accounts.from_last_page, request.to)?;
+        remove_from_index(&mut ctx.accounts.to_index, &mut ctx.accounts.to_page, &mut ctx.accounts.to_last_page, request.from)?;
+        Ok(())
+    }
+
+    pub fn close_deactivated_request(ctx: Context<CloseDeactivatedRequest>) -> Result<()> {
+        deactivated_party_check(&ctx.accounts.user, &ctx.accounts.closed_profile, &ctx.accounts.request)
+    }
+
+    // emptied pages and indexes can be closed by anyone, the rent goes back to whoever created them
+    pub fn close_friend_page(_ctx: Context<CloseFriendPage>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn close_friend_index(_ctx: Context<CloseFriendIndex>) -> Result<()> {
+        Ok(())
+    }
+
 }
 
 #[derive(Accounts)]
@@ -106,6 +189,36 @@ pub struct MakeRequest<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(user1: Pubkey, user2: Pubkey)]
+pub struct MakeRequestSponsored<'info> {
+    /// CHECK: loaded by the instruction, or created once the sponsor funded it
+    #[account(
+        mut,
+        seeds = [&user1.to_bytes()[..32], &user2.to_bytes()[..32]],
+        bump
+    )]
+    pub request: UncheckedAccount<'info>,
+    #[account(
+        constraint = user1.to_bytes() > user2.to_bytes() @ ErrorCode::OrderMismatch,
+        constraint = user1 == user.key() ||
+                     user2 == user.key() @ ErrorCode::WrongPrivileges
+    )]
+    pub user: Signer<'info>,
+    /// CHECK: checked by the sponsorship program
+    pub sponsor: UncheckedAccount<'info>,
+    /// CHECK: checked by the sponsorship program
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: checked by the sponsorship program
+    #[account(mut)]
+    pub quota: UncheckedAccount<'info>,
+    /// CHECK: instructions sysvar, checked by the sponsorship program
+    pub instructions: UncheckedAccount<'info>,
+    pub sponsorship_program: Program<'info, Sponsorship>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct AcceptRequest<'info> {
     #[account(
@@ -114,7 +227,102 @@ pub struct AcceptRequest<'info> {
         constraint = request.status == Status::Pending @ ErrorCode::NotPendingRequest
     )]
     pub request: Account<'info, FriendRequest>,
-    pub user: Signer<'info>
+    // the marker is closed whenever the friendship ends, so it only exists here for requests that are not pending
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = IndexedFriendship::LEN,
+        seeds = [&request.key().to_bytes()[..32], INDEXED_FRIENDSHIP_PDA_SEED],
+        bump
+    )]
+    pub indexed: Account<'info, IndexedFriendship>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FriendIndex::LEN,
+        seeds = [&request.from.to_bytes()[..32], FRIEND_INDEX_PDA_SEED],
+        bump
+    )]
+    pub from_index: Box<Account<'info, FriendIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FriendPage::LEN,
+        seeds = [&request.from.to_bytes()[..32], &index_page(from_index.friends).to_le_bytes(), FRIEND_PAGE_PDA_SEED],
+        bump
+    )]
+    pub from_page: Box<Account<'info, FriendPage>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FriendIndex::LEN,
+        seeds = [&request.to.to_bytes()[..32], FRIEND_INDEX_PDA_SEED],
+        bump
+    )]
+    pub to_index: Box<Account<'info, FriendIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FriendPage::LEN,
+        seeds = [&request.to.to_bytes()[..32], &index_page(to_index.friends).to_le_bytes(), FRIEND_PAGE_PDA_SEED],
+        bump
+    )]
+    pub to_page: Box<Account<'info, FriendPage>>,
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IndexFriendship<'info> {
+    #[account(
+        constraint = request.status == Status::Accepted @ ErrorCode::NotFriends
+    )]
+    pub request: Account<'info, FriendRequest>,
+    #[account(
+        init,
+        payer = payer,
+        space = IndexedFriendship::LEN,
+        seeds = [&request.key().to_bytes()[..32], INDEXED_FRIENDSHIP_PDA_SEED],
+        bump
+    )]
+    pub indexed: Account<'info, IndexedFriendship>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FriendIndex::LEN,
+        seeds = [&request.from.to_bytes()[..32], FRIEND_INDEX_PDA_SEED],
+        bump
+    )]
+    pub from_index: Box<Account<'info, FriendIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FriendPage::LEN,
+        seeds = [&request.from.to_bytes()[..32], &index_page(from_index.friends).to_le_bytes(), FRIEND_PAGE_PDA_SEED],
+        bump
+    )]
+    pub from_page: Box<Account<'info, FriendPage>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FriendIndex::LEN,
+        seeds = [&request.to.to_bytes()[..32], FRIEND_INDEX_PDA_SEED],
+        bump
+    )]
+    pub to_index: Box<Account<'info, FriendIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FriendPage::LEN,
+        seeds = [&request.to.to_bytes()[..32], &index_page(to_index.friends).to_le_bytes(), FRIEND_PAGE_PDA_SEED],
+        bump
+    )]
+    pub to_page: Box<Account<'info, FriendPage>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
@@ -141,6 +349,20 @@ pub struct RemoveRequest<'info> {
    
 }
 
+#[derive(Accounts)]
+pub struct TransferPayer<'info> {
+    #[account(
+        mut,
+        constraint = user.key() == request.from ||
+                     user.key() == request.to @ ErrorCode::WrongPrivileges,
+        constraint = request.payer == payer.key() @ ErrorCode::PayerMismatch,
+    )]
+    pub request: Account<'info, FriendRequest>,
+    pub user: Signer<'info>,
+    pub payer: Signer<'info>,
+    pub new_payer: SystemAccount<'info>
+}
+
 #[derive(Accounts)]
 pub struct CloseRequest<'info> {
     #[account(
@@ -167,7 +389,149 @@ pub struct RemoveFriend<'info> {
         constraint = request.status == Status::Accepted @ ErrorCode::NotFriends
     )]
     pub request: Account<'info, FriendRequest>,
-    pub user: Signer<'info>
+    #[account(
+        mut,
+        seeds = [&request.from.to_bytes()[..32], FRIEND_INDEX_PDA_SEED],
+        bump
+    )]
+    pub from_index: Box<Account<'info, FriendIndex>>,
+    #[account(
+        mut,
+        constraint = from_page.user == request.from @ ErrorCode::IndexMismatch
+    )]
+    pub from_page: Box<Account<'info, FriendPage>>,
+    #[account(
+        mut,
+        seeds = [&request.from.to_bytes()[..32], &index_page(from_index.friends.saturating_sub(1)).to_le_bytes(), FRIEND_PAGE_PDA_SEED],
+        bump
+    )]
+    pub from_last_page: Box<Account<'info, FriendPage>>,
+    #[account(
+        mut,
+        seeds = [&request.to.to_bytes()[..32], FRIEND_INDEX_PDA_SEED],
+        bump
+    )]
+    pub to_index: Box<Account<'info, FriendIndex>>,
+    #[account(
+        mut,
+        constraint = to_page.user == request.to @ ErrorCode::IndexMismatch
+    )]
+    pub to_page: Box<Account<'info, FriendPage>>,
+    #[account(
+        mut,
+        seeds = [&request.to.to_bytes()[..32], &index_page(to_index.friends.saturating_sub(1)).to_le_bytes(), FRIEND_PAGE_PDA_SEED],
+        bump
+    )]
+    pub to_last_page: Box<Account<'info, FriendPage>>,
+    pub user: Signer<'info>,
+    /// CHECK: closed by `close_indexed`, which rejects friendships that were never indexed
+    #[account(
+        mut,
+        seeds = [&request.key().to_bytes()[..32], INDEXED_FRIENDSHIP_PDA_SEED],
+        bump
+    )]
+    pub indexed: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub indexed_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveDeactivatedFriend<'info> {
+    #[account(
+        mut,
+        constraint = request.status == Status::Accepted @ ErrorCode::NotFriends
+    )]
+    pub request: Account<'info, FriendRequest>,
+    /// CHECK: validated by `deactivated_party_check`
+    pub user: UncheckedAccount<'info>,
+    /// CHECK: validated by `deactivated_party_check`
+    pub closed_profile: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [&request.from.to_bytes()[..32], FRIEND_INDEX_PDA_SEED],
+        bump
+    )]
+    pub from_index: Box<Account<'info, FriendIndex>>,
+    #[account(
+        mut,
+        constraint = from_page.user == request.from @ ErrorCode::IndexMismatch
+    )]
+    pub from_page: Box<Account<'info, FriendPage>>,
+    #[account(
+        mut,
+        seeds = [&request.from.to_bytes()[..32], &index_page(from_index.friends.saturating_sub(1)).to_le_bytes(), FRIEND_PAGE_PDA_SEED],
+        bump
+    )]
+    pub from_last_page: Box<Account<'info, FriendPage>>,
+    #[account(
+        mut,
+        seeds = [&request.to.to_bytes()[..32], FRIEND_INDEX_PDA_SEED],
+        bump
+    )]
+    pub to_index: Box<Account<'info, FriendIndex>>,
+    #[account(
+        mut,
+        constraint = to_page.user == request.to @ ErrorCode::IndexMismatch
+    )]
+    pub to_page: Box<Account<'info, FriendPage>>,
+    #[account(
+        mut,
+        seeds = [&request.to.to_bytes()[..32], &index_page(to_index.friends.saturating_sub(1)).to_le_bytes(), FRIEND_PAGE_PDA_SEED],
+        bump
+    )]
+    pub to_last_page: Box<Account<'info, FriendPage>>,
+    /// CHECK: closed by `close_indexed`, which rejects friendships that were never indexed
+    #[account(
+        mut,
+        seeds = [&request.key().to_bytes()[..32], INDEXED_FRIENDSHIP_PDA_SEED],
+        bump
+    )]
+    pub indexed: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub indexed_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDeactivatedRequest<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = request.status != Status::Accepted @ ErrorCode::AlreadyFriends,
+        constraint = request.payer == payer.key() @ ErrorCode::PayerMismatch,
+    )]
+    pub request: Account<'info, FriendRequest>,
+    /// CHECK: validated by `deactivated_party_check`
+    pub user: UncheckedAccount<'info>,
+    /// CHECK: validated by `deactivated_party_check`
+    pub closed_profile: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>
+}
+
+#[derive(Accounts)]
+pub struct CloseFriendPage<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = page.friends.is_empty() @ ErrorCode::PageNotEmpty,
+        constraint = page.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub page: Account<'info, FriendPage>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>
+}
+
+#[derive(Accounts)]
+pub struct CloseFriendIndex<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = index.friends == 0 @ ErrorCode::PageNotEmpty,
+        constraint = index.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub index: Account<'info, FriendIndex>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>
 }
 
 #[account]
@@ -190,6 +554,46 @@ impl FriendRequest {
     + STRING_LENGTH_PREFIX + STRING_LENGTH_TO_ENCRYPTED_KEY;
 }
 
+#[account]
+pub struct FriendIndex {
+    pub user: Pubkey,
+    pub friends: u32,
+    pub payer: Pubkey,
+}
+
+impl FriendIndex {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_USER_LENGTH
+    + U32_LENGTH
+    + PUBKEY_PAYER_LENGTH;
+}
+
+#[account]
+pub struct FriendPage {
+    pub user: Pubkey,
+    pub page: u32,
+    pub friends: Vec<Pubkey>,
+    pub payer: Pubkey,
+}
+
+impl FriendPage {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_USER_LENGTH
+    + U32_LENGTH
+    + VEC_LENGTH_PREFIX + PUBKEY_FRIEND_LENGTH * FRIEND_PAGE_CAPACITY
+    + PUBKEY_PAYER_LENGTH;
+}
+
+#[account]
+pub struct IndexedFriendship {
+    pub payer: Pubkey,
+}
+
+impl IndexedFriendship {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_PAYER_LENGTH;
+}
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("Addresses in request don't match user address")]
@@ -212,4 +616,117 @@ pub enum ErrorCode {
     NotRemoved,
     #[msg("Request is already removed")]
     AlreadyRemoved,
-}
\ No newline at end of file
+    #[msg("Friend index does not match request")]
+    IndexMismatch,
+    #[msg("Friendship is not indexed yet")]
+    NotIndexed,
+    #[msg("User profile is not deactivated")]
+    NotDeactivated,
+    #[msg("Index still has entries")]
+    PageNotEmpty,
+}
+
+// requests are seeded by the two keys in descending order, whoever sent it
+pub fn friend_request_address(user: &Pubkey, friend: &Pubkey) -> Pubkey {
+    let (user1, user2) = if user.to_bytes() > friend.to_bytes() { (user, friend) } else { (friend, user) };
+    Pubkey::find_program_address(&[&user1.to_bytes()[..32], &user2.to_bytes()[..32]], &ID).0
+}
+
+// true only for the accepted request between the two users, owned by this program
+pub fn verify_friendship(request: &AccountInfo, user: &Pubkey, friend: &Pubkey) -> bool {
+    if request.key() != friend_request_address(user, friend) || *request.owner != ID {
+        return false
+    }
+
+    let data = match request.try_borrow_data() {
+        Ok(data) => data,
+        Err(_) => return false
+    };
+    match FriendRequest::try_deserialize(&mut &data[..]) {
+        Ok(request) => request.status == Status::Accepted,
+        Err(_) => false
+    }
+}
+
+// `user` is the sender, the request keeps its `payer` when it is reused
+fn open_request(request: &mut FriendRequest, user: Pubkey, user1: Pubkey, user2: Pubkey, k: String) {
+    if user == user1 {
+        request.from = user1;
+        request.to = user2;
+    } else {
+        request.from = user2;
+        request.to = user1;
+    }
+    request.from_encrypted_key = k;
+    request.to_encrypted_key = "".to_string();
+    request.status = Status::Pending;
+}
+
+fn index_page(position: u32) -> u32 {
+    position / FRIEND_PAGE_CAPACITY as u32
+}
+
+// an index or page keeps the payer that created it, even when others reuse it later
+fn add_to_index(index: &mut FriendIndex, page: &mut FriendPage, user: Pubkey, friend: Pubkey, payer: Pubkey) -> Result<()> {
+    if page.friends.len() >= FRIEND_PAGE_CAPACITY {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+    if index.payer == Pubkey::default() {
+        index.payer = payer;
+    }
+    if page.payer == Pubkey::default() {
+        page.payer = payer;
+    }
+
+    index.user = user;
+    page.user = user;
+    page.page = index_page(index.friends);
+    page.friends.push(friend);
+    index.friends += 1;
+
+    Ok(())
+}
+
+// friendships accepted before the index existed have no marker and need `index_friendship` first
+fn close_indexed<'info>(indexed: &AccountInfo<'info>, indexed_payer: &AccountInfo<'info>) -> Result<()> {
+    if indexed.owner != &ID || indexed.lamports() == 0 {
+        return Err(error!(ErrorCode::NotIndexed))
+    }
+
+    let marker: Account<IndexedFriendship> = Account::try_from(indexed)?;
+    if marker.payer != indexed_payer.key() {
+        return Err(error!(ErrorCode::PayerMismatch))
+    }
+    marker.close(indexed_payer.clone())
+}
+
+fn remove_from_index(index: &mut FriendIndex, page: &mut FriendPage, last_page: &mut FriendPage, friend: Pubkey) -> Result<()> {
+    let position = match page.friends.iter().position(|key| *key == friend) {
+        Some(position) => position,
+        None => return Err(error!(ErrorCode::IndexMismatch))
+    };
+
+    // the same page can be passed twice, in that case both copies have to end up identical
+    if page.page == last_page.page {
+        page.friends.swap_remove(position);
+        last_page.friends = page.friends.clone();
+    } else {
+        match last_page.friends.pop() {
+            Some(last) => page.friends[position] = last,
+            None => return Err(error!(ErrorCode::IndexMismatch))
+        }
+    }
+    index.friends -= 1;
+
+    Ok(())
+}
+
+// `profile` and `closed_profile` have to belong to a side of `request` that is leaving
+fn deactivated_party_check(profile: &AccountInfo, closed_profile: &AccountInfo, request: &FriendRequest) -> Result<()> {
+    let party = verify_deactivated_profile(profile, closed_profile, &request.from).or_else(|| verify_deactivated_profile(profile, closed_profile, &request.to));
+    match party {
+        Some(true) => Ok(()),
+        Some(false) => Err(error!(ErrorCode::NotDeactivated)),
+        None => Err(error!(ErrorCode::WrongRequestData))
+    }
+}
@@ -1,9 +1,41 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::{system_program, AccountsClose, Discriminator};
+use users::verify_deactivated_profile;
+use friends::verify_friendship;
+use sponsorship::program::Sponsorship;
+use sponsorship::create_sponsored_account;
 
 declare_id!("EGSLBxSQyf8aYgV8gavHy2qhdwdrCRunap6Tv3c7YJWP");
 
 const GROUP_PDA_SEED: &[u8] = b"groupchat";
 const INVITE_PDA_SEED: &[u8] = b"invite";
+const MEMBERSHIP_INDEX_PDA_SEED: &[u8] = b"membership";
+const MEMBERSHIP_PAGE_PDA_SEED: &[u8] = b"membershippage";
+const MEMBERSHIP_PAGE_CAPACITY: usize = 32;
+const ROSTER_PAGE_PDA_SEED: &[u8] = b"roster";
+const ROSTER_PAGE_CAPACITY: usize = 32;
+const INVITE_LINK_PDA_SEED: &[u8] = b"invitelink";
+const JOIN_REQUEST_PDA_SEED: &[u8] = b"joinrequest";
+const DIRECTORY_PDA_SEED: &[u8] = b"directory";
+const DIRECTORY_PAGE_PDA_SEED: &[u8] = b"directorypage";
+const DIRECTORY_PAGE_CAPACITY: usize = 32;
+const PINS_PDA_SEED: &[u8] = b"pins";
+const MAX_PINS: usize = 8;
+const CHANNEL_PDA_SEED: &[u8] = b"channel";
+const PROPOSAL_PDA_SEED: &[u8] = b"proposal";
+const VOTE_PDA_SEED: &[u8] = b"vote";
+// share of the members that have to vote for a proposal to be valid
+const QUORUM_PERCENT: u32 = 50;
+// time after the deadline during which an accepted proposal stays open for execution
+const EXECUTION_WINDOW_SECONDS: i64 = 60 * 60 * 24 * 7;
+const MULTISIG_PDA_SEED: &[u8] = b"multisig";
+const MULTISIG_TRANSACTION_PDA_SEED: &[u8] = b"multisigtx";
+const MAX_MULTISIG_OWNERS: usize = 10;
+const MAX_MULTISIG_ACCOUNTS: usize = 16;
+const MAX_MULTISIG_DATA: usize = 512;
 const DISCRIMINATOR_LENGTH: usize = 8;
 const PUBKEY_LENGTH: usize = 32; 
 const BOOL_LENGTH: usize = 1; 
@@ -12,61 +44,285 @@ const STRING_LENGTH_NAME: usize = 64;
 const STRING_LENGTH_GROUP_ID: usize = 160;
 const U8_LENGTH: usize = 1;
 const STRING_LENGTH_ENCRYPTION_KEY: usize = 64;
-const DB_TYPE_LENGTH: usize = 1;
+const STRING_LENGTH_DESCRIPTION: usize = 128;
+const STRING_LENGTH_TAG: usize = 16;
+const STRING_LENGTH_AVATAR_HASH: usize = 64;
+const STRING_LENGTH_TOPIC: usize = 64;
+const STRING_LENGTH_CONTENT_HASH: usize = 64;
+const STRING_LENGTH_CHANNEL_NAME: usize = 32;
+const MAX_TAGS: usize = 5;
+const VISIBILITY_LENGTH: usize = 1;
+const ROLE_LENGTH: usize = 1;
+const PROPOSAL_ACTION_LENGTH: usize = 1 + STRING_LENGTH_PREFIX + STRING_LENGTH_NAME;
+const TRANSACTION_ACCOUNT_LENGTH: usize = PUBKEY_LENGTH + BOOL_LENGTH + BOOL_LENGTH;
+const DB_TYPE_LENGTH: usize = 2;
+const DB_TYPE_VERSION: u8 = 1;
+const LEGACY_GROUP_LEN: usize = 142;
+const LEGACY_INVITATION_LEN: usize = 337;
+const U32_LENGTH: usize = 4;
+const VEC_LENGTH_PREFIX: usize = 4;
+const HASH_LENGTH: usize = 32;
+const U16_LENGTH: usize = 2;
+const I64_LENGTH: usize = 8;
+const CRANK_REWARD_LAMPORTS: u64 = 5000;
+
+// new backends bump DB_TYPE_VERSION, `Extension` covers backends not known to this version
+#[derive(Clone, Copy, AnchorSerialize, AnchorDeserialize, PartialEq)]
+pub enum DbType {
+    Unknown,
+    Textile,
+    Gun,
+    Extension(u8),
+}
+
+#[derive(Clone, Copy, AnchorSerialize, AnchorDeserialize, PartialEq)]
+pub enum Visibility {
+    Private,
+    Public,
+}
+
+// the admin is tracked on `Group`, roles only distinguish the other members
+#[derive(Clone, Copy, AnchorSerialize, AnchorDeserialize, PartialEq)]
+pub enum Role {
+    Member,
+    Moderator,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize, PartialEq)]
+pub struct TransactionAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize, PartialEq)]
+pub enum ProposalAction {
+    Rename { name: String },
+    ChangeAdmin { new_admin: Pubkey },
+    Dissolve,
+}
 
 #[program]
 pub mod groupchats {
     use super::*;
 
-    pub fn create(ctx: Context<Create>, _group_hash: [u8; 32], group_id: String, open_invites: bool, name: String, encryption_key: String, db_type: u8) -> Result<()> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn create<'info>(ctx: Context<'_, '_, '_, 'info, Create<'info>>, group_hash: [u8; 32], group_id: String, open_invites: bool, name: String, encryption_key: String, db_type: DbType, visibility: Visibility, category: u8, description: String, tags: Vec<String>) -> Result<()> {
+        db_type_check(db_type)?;
+
         let group = &mut ctx.accounts.group;
         let invitation = &mut ctx.accounts.invitation;
-        group.creator = ctx.accounts.payer.key();
+        group.creator = ctx.accounts.signer.key();
+        group.payer = ctx.accounts.payer.key();
         group.admin = ctx.accounts.signer.key();
         group.open_invites = open_invites;
         group.members = 1;
+        group.roster_pages = 1;
+        group.db_type = db_type;
+        group.db_type_version = DB_TYPE_VERSION;
         
         length_check(&name, 3, 64, true)?;
         group.name = name;
 
-        invitation.sender = ctx.accounts.payer.key();
+        group.visibility = visibility;
+        group.category = category;
+
+        length_check(&description, 0, 128, false)?;
+        group.description = description;
+        group.avatar_hash = "".to_string();
+        group.topic = "".to_string();
+
+        tags_check(&tags)?;
+        group.tags = tags;
+
+        invitation.sender = ctx.accounts.signer.key();
+        invitation.payer = ctx.accounts.payer.key();
         invitation.group_key = group.key();
         invitation.recipient = ctx.accounts.signer.key();
 
         length_check(&group_id, 1, 160, true)?;
-        invitation.group_id = group_id;
+        group_hash_check(&group_hash, &group_id, &ctx.accounts.signer.key())?;
+        group.group_id = group_id;
 
         length_check(&encryption_key, 64, 64, true)?;
         invitation.encryption_key = encryption_key;
 
-        invitation.db_type = db_type;
+        add_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, ctx.accounts.signer.key(), group.key(), ctx.accounts.payer.key())?;
+        add_to_roster(&mut ctx.accounts.roster_page, 0, group.key(), ctx.accounts.signer.key(), ctx.accounts.payer.key())?;
+
+        if group.visibility == Visibility::Public {
+            list_group(ctx.remaining_accounts, category, group.key(), &ctx.accounts.payer, &ctx.accounts.system_program)?;
+        }
+
+        emit!(GroupCreated {
+            group: group.key(),
+            admin: group.admin,
+            db_type: group.db_type,
+            db_type_version: group.db_type_version,
+        });
 
         Ok(())
     }
 
-    pub fn invite(ctx: Context<Invite>, group_id: String, recipient: Pubkey, encryption_key: String, db_type: u8) -> Result<()> {
+    // with `invite_only_friends` the accepted friend request is passed as the first remaining account
+    pub fn invite(ctx: Context<Invite>, recipient: Pubkey, encryption_key: String) -> Result<()> {
+        invite_only_friends_check(&ctx.accounts.group, ctx.remaining_accounts, &ctx.accounts.signer.key(), &recipient)?;
+
+        let new_invitation = &mut ctx.accounts.new_invitation;
+        new_invitation.sender = ctx.accounts.signer.key();
+        new_invitation.payer = ctx.accounts.payer.key();
+        invite_member(&mut ctx.accounts.group, new_invitation, &mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, &mut ctx.accounts.roster_page, recipient, encryption_key)
+    }
+
+    // same as `invite`, with the rent of every account it creates drawn from the sponsor's vault, which the invitation and any page it creates record as their payer
+    pub fn invite_sponsored<'info>(ctx: Context<'_, '_, '_, 'info, InviteSponsored<'info>>, recipient: Pubkey, encryption_key: String) -> Result<()> {
+        invite_only_friends_check(&ctx.accounts.group, ctx.remaining_accounts, &ctx.accounts.signer.key(), &recipient)?;
+
+        let group_key = ctx.accounts.group.key();
+        let accounts = &ctx.accounts;
+        let mut new_invitation: Account<Invitation> = create_sponsored(accounts, &accounts.new_invitation, &[&recipient.to_bytes()[..32], &group_key.to_bytes()[..32], INVITE_PDA_SEED], Invitation::LEN)?;
+        let mut membership_index: Account<MembershipIndex> = load_or_create_sponsored(accounts, &accounts.membership_index, &[&recipient.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED], MembershipIndex::LEN)?;
+        let page_number = membership_page_number(membership_index.groups);
+        let mut membership_page: Account<MembershipPage> = load_or_create_sponsored(accounts, &accounts.membership_page, &[&recipient.to_bytes()[..32], &page_number.to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED], MembershipPage::LEN)?;
+        let page_number = roster_page_number(accounts.group.members);
+        let mut roster_page: Account<RosterPage> = load_or_create_sponsored(accounts, &accounts.roster_page, &[&group_key.to_bytes()[..32], &page_number.to_le_bytes(), ROSTER_PAGE_PDA_SEED], RosterPage::LEN)?;
+
+        new_invitation.sender = ctx.accounts.signer.key();
+        new_invitation.payer = ctx.accounts.vault.key();
+        invite_member(&mut ctx.accounts.group, &mut new_invitation, &mut membership_index, &mut membership_page, &mut roster_page, recipient, encryption_key)?;
+
+        new_invitation.exit(&crate::ID)?;
+        membership_index.exit(&crate::ID)?;
+        membership_page.exit(&crate::ID)?;
+        roster_page.exit(&crate::ID)
+    }
+
+    pub fn create_invite_link(ctx: Context<CreateInviteLink>, code_hash: [u8; 32], max_uses: u16, expires_at: i64) -> Result<()> {
+        if max_uses == 0 || expires_at <= Clock::get()?.unix_timestamp {
+            return Err(error!(ErrorCode::IncorrectField))
+        }
+
+        let invite_link = &mut ctx.accounts.invite_link;
+        invite_link.group = ctx.accounts.group.key();
+        invite_link.payer = ctx.accounts.payer.key();
+        invite_link.code_hash = code_hash;
+        invite_link.max_uses = max_uses;
+        invite_link.uses = 0;
+        invite_link.expires_at = expires_at;
+
+        Ok(())
+    }
+
+    pub fn join_with_link(ctx: Context<JoinWithLink>, code: String) -> Result<()> {
+        let invite_link = &mut ctx.accounts.invite_link;
+
+        length_check(&code, 16, 64, true)?;
+        if hashv(&[code.as_bytes()]).to_bytes() != invite_link.code_hash {
+            return Err(error!(ErrorCode::InvalidInviteCode))
+        }
+        if Clock::get()?.unix_timestamp >= invite_link.expires_at {
+            return Err(error!(ErrorCode::InviteLinkExpired))
+        }
+        if invite_link.uses >= invite_link.max_uses {
+            return Err(error!(ErrorCode::InviteLinkExhausted))
+        }
+        invite_link.uses += 1;
+
         let group = &mut ctx.accounts.group;
         let new_invitation = &mut ctx.accounts.new_invitation;
-        group.members += 1;
-        new_invitation.sender = ctx.accounts.payer.key();
+        let recipient = ctx.accounts.signer.key();
+        add_member(group, &mut ctx.accounts.roster_page, recipient, ctx.accounts.payer.key())?;
+        new_invitation.sender = ctx.accounts.signer.key();
+        new_invitation.payer = ctx.accounts.payer.key();
         new_invitation.group_key = group.key();
         new_invitation.recipient = recipient;
+        // the encryption key is delivered later by a member through `deliver_key`
+        new_invitation.encryption_key = "".to_string();
 
-        length_check(&group_id, 1, 160, true)?;
-        new_invitation.group_id = group_id;
+        add_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, recipient, group.key(), ctx.accounts.payer.key())?;
+
+        emit!(MemberInvited {
+            group: group.key(),
+            recipient,
+            db_type: group.db_type,
+            db_type_version: group.db_type_version,
+        });
+
+        Ok(())
+    }
+
+    pub fn deliver_key(ctx: Context<DeliverKey>, encryption_key: String) -> Result<()> {
+        let recipient_invitation = &mut ctx.accounts.recipient_invitation;
+
+        length_check(&encryption_key, 64, 64, true)?;
+        recipient_invitation.encryption_key = encryption_key;
+
+        Ok(())
+    }
+
+    pub fn close_invite_link(_ctx: Context<CloseInviteLink>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn request_to_join(ctx: Context<RequestToJoin>) -> Result<()> {
+        let join_request = &mut ctx.accounts.join_request;
+        join_request.group = ctx.accounts.group.key();
+        join_request.requester = ctx.accounts.signer.key();
+        join_request.payer = ctx.accounts.payer.key();
+        Ok(())
+    }
+
+    pub fn approve_join(ctx: Context<ApproveJoin>, encryption_key: String) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        let new_invitation = &mut ctx.accounts.new_invitation;
+        let recipient = ctx.accounts.join_request.requester;
+        add_member(group, &mut ctx.accounts.roster_page, recipient, ctx.accounts.payer.key())?;
+        new_invitation.sender = ctx.accounts.admin.key();
+        new_invitation.payer = ctx.accounts.payer.key();
+        new_invitation.group_key = group.key();
+        new_invitation.recipient = recipient;
 
         length_check(&encryption_key, 64, 64, true)?;
         new_invitation.encryption_key = encryption_key;
 
-        new_invitation.db_type = db_type;
-        
+        add_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, recipient, group.key(), ctx.accounts.payer.key())?;
+
+        emit!(MemberInvited {
+            group: group.key(),
+            recipient,
+            db_type: group.db_type,
+            db_type_version: group.db_type_version,
+        });
+
+        Ok(())
+    }
+
+    pub fn reject_join(_ctx: Context<RejectJoin>) -> Result<()> {
         Ok(())
     }
 
-    pub fn modify_successor(ctx: Context<ModifySuccessor>) -> Result<()> {
+    // signed by the requester, or by anyone once the group was dissolved or closed
+    pub fn cancel_join_request(_ctx: Context<CancelJoinRequest>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn propose_admin(ctx: Context<ModifySuccessor>) -> Result<()> {
         let group = &mut ctx.accounts.group;
         let successor = &mut ctx.accounts.successor;
-        group.admin = successor.recipient;
+        group.pending_admin = successor.recipient;
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        group.admin = ctx.accounts.signer.key();
+        group.pending_admin = Pubkey::default();
+        Ok(())
+    }
+
+    pub fn cancel_admin_proposal(ctx: Context<ModifyParameter>) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        group.pending_admin = Pubkey::default();
         Ok(())
     }
 
@@ -76,18 +332,161 @@ pub mod groupchats {
         Ok(())
     }
 
+    pub fn modify_invite_only_friends(ctx: Context<ModifyParameter>, invite_only_friends: bool) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        group.invite_only_friends = invite_only_friends;
+        Ok(())
+    }
+
+    // relays only accept posts from the admin and moderators while this is set
+    pub fn modify_announcement_only(ctx: Context<ModifyParameter>, announcement_only: bool) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        group.announcement_only = announcement_only;
+        Ok(())
+    }
+
+    // a timestamp in the past lifts the mute
+    pub fn mute(ctx: Context<Mute>, muted_until: i64) -> Result<()> {
+        let member_invitation = &mut ctx.accounts.member_invitation;
+        member_invitation.muted_until = muted_until;
+        Ok(())
+    }
+
     pub fn modify_name(ctx: Context<ModifyParameter>, name: String) -> Result<()> {
         let group = &mut ctx.accounts.group;
 
-        length_check(&name, 3, 64, true)?;
-        group.name = name;
+        rename_group(group, name)
+    }
+
+    pub fn set_role(ctx: Context<SetRole>, role: Role) -> Result<()> {
+        let invitation = &mut ctx.accounts.invitation;
+        invitation.role = role;
+        Ok(())
+    }
+
+    pub fn set_avatar_hash(ctx: Context<ModifyProfile>, avatar_hash: String) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+
+        length_check(&avatar_hash, 64, 64, false)?;
+        group.avatar_hash = avatar_hash;
+
+        Ok(())
+    }
+
+    pub fn set_description(ctx: Context<ModifyProfile>, description: String) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+
+        length_check(&description, 0, 128, false)?;
+        group.description = description;
+
+        Ok(())
+    }
+
+    pub fn set_topic(ctx: Context<ModifyProfile>, topic: String) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+
+        length_check(&topic, 0, 64, false)?;
+        group.topic = topic;
+
+        Ok(())
+    }
+
+    pub fn pin(ctx: Context<Pin>, content_hash: String) -> Result<()> {
+        let pins = &mut ctx.accounts.pins;
+
+        length_check(&content_hash, 64, 64, true)?;
+        if pins.items.contains(&content_hash) {
+            return Err(error!(ErrorCode::AlreadyPinned))
+        }
+        if pins.items.len() >= MAX_PINS {
+            return Err(error!(ErrorCode::TooManyPins))
+        }
+
+        pins.group = ctx.accounts.group.key();
+        record_payer(&mut pins.payer, ctx.accounts.payer.key());
+        pins.items.push(content_hash.clone());
+
+        emit!(PinAdded {
+            group: pins.group,
+            content_hash,
+            pinned_by: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn unpin(ctx: Context<Unpin>, content_hash: String) -> Result<()> {
+        let pins = &mut ctx.accounts.pins;
+
+        let position = match pins.items.iter().position(|item| *item == content_hash) {
+            Some(position) => position,
+            None => return Err(error!(ErrorCode::NotPinned))
+        };
+        pins.items.remove(position);
+
+        emit!(PinRemoved {
+            group: pins.group,
+            content_hash,
+            unpinned_by: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn create_channel(ctx: Context<CreateChannel>, channel_id: u16, name: String, encryption_key: String, min_role: Role) -> Result<()> {
+        let channel = &mut ctx.accounts.channel;
+        channel.group = ctx.accounts.group.key();
+        channel.channel_id = channel_id;
+        channel.payer = ctx.accounts.payer.key();
+        channel.min_role = min_role;
+
+        length_check(&name, 3, 32, true)?;
+        channel.name = name;
+
+        // an empty key means the channel shares the group key
+        length_check(&encryption_key, 64, 64, false)?;
+        channel.encryption_key = encryption_key;
+
+        Ok(())
+    }
+
+    pub fn rename_channel(ctx: Context<ModifyChannel>, name: String) -> Result<()> {
+        let channel = &mut ctx.accounts.channel;
 
+        length_check(&name, 3, 32, true)?;
+        channel.name = name;
+
+        Ok(())
+    }
+
+    pub fn set_channel_role(ctx: Context<ModifyChannel>, min_role: Role) -> Result<()> {
+        let channel = &mut ctx.accounts.channel;
+        channel.min_role = min_role;
+        Ok(())
+    }
+
+    pub fn delete_channel(_ctx: Context<DeleteChannel>) -> Result<()> {
         Ok(())
     }
 
     pub fn leave(ctx: Context<Leave>) -> Result<()> {
         let group = &mut ctx.accounts.group;
         group.members -= 1;
+        remove_from_roster(&mut ctx.accounts.roster_page, &mut ctx.accounts.roster_last_page, ctx.accounts.invitation.recipient)?;
+        remove_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, &mut ctx.accounts.membership_last_page, group.key())?;
+        Ok(())
+    }
+
+    // permissionless once the member deactivated or closed its profile, admins have to hand the group over first
+    // a deactivated admin can't hand the group over, so removing them dissolves it
+    pub fn remove_deactivated_member<'info>(ctx: Context<'_, '_, '_, 'info, RemoveDeactivatedMember<'info>>) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        if ctx.accounts.invitation.recipient == group.admin && !group.dissolved {
+            dissolve_group(group, ctx.remaining_accounts)?;
+        }
+        group.members -= 1;
+        remove_from_roster(&mut ctx.accounts.roster_page, &mut ctx.accounts.roster_last_page, ctx.accounts.invitation.recipient)?;
+        remove_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, &mut ctx.accounts.membership_last_page, group.key())?;
         Ok(())
     }
 
@@ -95,10 +494,353 @@ pub mod groupchats {
         let group = &mut ctx.accounts.group;
         group.members -= 1;
         group.admin = ctx.accounts.successor.recipient;
+        group.pending_admin = Pubkey::default();
+        remove_from_roster(&mut ctx.accounts.roster_page, &mut ctx.accounts.roster_last_page, ctx.accounts.invitation.recipient)?;
+        remove_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, &mut ctx.accounts.membership_last_page, group.key())?;
+        Ok(())
+    }
+
+    pub fn transfer_payer(ctx: Context<TransferPayer>) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        group.payer = ctx.accounts.new_payer.key();
+        Ok(())
+    }
+
+    // the rent is the payer's alone, so members don't need to co-sign
+    pub fn transfer_invitation_payer(ctx: Context<TransferInvitationPayer>) -> Result<()> {
+        let invitation = &mut ctx.accounts.invitation;
+        invitation.payer = ctx.accounts.new_payer.key();
+        Ok(())
+    }
+
+    pub fn close<'info>(ctx: Context<'_, '_, '_, 'info, Close<'info>>) -> Result<()> {
+        let group_key = ctx.accounts.group.key();
+        remove_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, &mut ctx.accounts.membership_last_page, group_key)?;
+        // dissolved groups were already delisted by `dissolve`
+        if ctx.accounts.group.visibility == Visibility::Public && !ctx.accounts.group.dissolved {
+            delist_group(ctx.remaining_accounts, &ctx.accounts.group)?;
+            return close_roster_pages(&ctx.remaining_accounts[3..], &ctx.accounts.group)
+        }
+        close_roster_pages(ctx.remaining_accounts, &ctx.accounts.group)
+    }
+
+    // once dissolved, anyone can close the remaining invitations through `leave`
+    pub fn dissolve<'info>(ctx: Context<'_, '_, '_, 'info, Dissolve<'info>>) -> Result<()> {
+        dissolve_group(&mut ctx.accounts.group, ctx.remaining_accounts)
+    }
+
+    // roster pages past the first are expected as remaining accounts, in order, each followed by its payer
+    pub fn close_dissolved<'info>(ctx: Context<'_, '_, '_, 'info, CloseDissolved<'info>>) -> Result<()> {
+        close_roster_pages(ctx.remaining_accounts, &ctx.accounts.group)
+    }
+
+    // emptied pages, indexes and pins can be closed by anyone, the rent goes back to whoever created them
+    pub fn close_membership_page(_ctx: Context<CloseMembershipPage>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn close_membership_index(_ctx: Context<CloseMembershipIndex>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn close_directory_page(_ctx: Context<CloseDirectoryPage>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn close_directory(_ctx: Context<CloseDirectory>) -> Result<()> {
+        Ok(())
+    }
+
+    // pins of a dissolved or closed group can be closed by anyone even when not empty
+    pub fn close_pins(_ctx: Context<ClosePins>) -> Result<()> {
+        Ok(())
+    }
+
+    // invite links and channels outlive their group, anyone can close them once it is dissolved or closed
+    pub fn reap_invite_link(_ctx: Context<ReapInviteLink>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn reap_channel(_ctx: Context<ReapChannel>) -> Result<()> {
+        Ok(())
+    }
+
+    // expects the roster page and last roster page of the group as remaining accounts
+    pub fn reap_invitation<'info>(ctx: Context<'_, '_, '_, 'info, ReapInvitation<'info>>, claim_reward: bool) -> Result<()> {
+        let group = &mut ctx.accounts.group;
+        let (mut roster_page, mut roster_last_page) = load_roster_pages(ctx.remaining_accounts, group)?;
+        remove_from_roster(&mut roster_page, &mut roster_last_page, ctx.accounts.invitation.recipient)?;
+        group.members -= 1;
+        roster_page.exit(&crate::ID)?;
+        roster_last_page.exit(&crate::ID)?;
+
+        remove_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, &mut ctx.accounts.membership_last_page, group.key())?;
+
+        // the rest of the rent goes back to the payer when the invitation closes
+        if claim_reward {
+            let invitation_info = ctx.accounts.invitation.to_account_info();
+            **invitation_info.try_borrow_mut_lamports()? -= CRANK_REWARD_LAMPORTS;
+            **ctx.accounts.cranker.try_borrow_mut_lamports()? += CRANK_REWARD_LAMPORTS;
+        }
+
+        Ok(())
+    }
+
+    // legacy invitations are neither in the roster nor in a membership index, and may outlive their group
+    pub fn reap_legacy_invitation(ctx: Context<ReapLegacyInvitation>, claim_reward: bool) -> Result<()> {
+        let legacy_invitation: LegacyInvitation = read_legacy(&ctx.accounts.invitation, LEGACY_INVITATION_LEN, Invitation::discriminator())?;
+        if legacy_invitation.group_key != ctx.accounts.group.key() {
+            return Err(error!(ErrorCode::InvitationMismatch))
+        }
+        if legacy_invitation.sender != ctx.accounts.invitation_payer.key() {
+            return Err(error!(ErrorCode::PayerMismatch))
+        }
+
+        let group_info = ctx.accounts.group.to_account_info();
+        if group_info.owner == &crate::ID && group_info.lamports() > 0 {
+            // a group still in the legacy layout cannot have been dissolved
+            if group_info.data_len() == LEGACY_GROUP_LEN {
+                return Err(error!(ErrorCode::GroupStillActive))
+            }
+            let group: Account<Group> = Account::try_from(&group_info)?;
+            if !group.dissolved {
+                return Err(error!(ErrorCode::GroupStillActive))
+            }
+        }
+
+        let invitation_info = ctx.accounts.invitation.to_account_info();
+        if claim_reward {
+            **invitation_info.try_borrow_mut_lamports()? -= CRANK_REWARD_LAMPORTS;
+            **ctx.accounts.cranker.try_borrow_mut_lamports()? += CRANK_REWARD_LAMPORTS;
+        }
+        close_legacy_account(&invitation_info, &ctx.accounts.invitation_payer)
+    }
+
+    pub fn create_proposal(ctx: Context<CreateProposal>, action: ProposalAction, deadline: i64) -> Result<()> {
+        if deadline <= Clock::get()?.unix_timestamp {
+            return Err(error!(ErrorCode::InvalidDeadline))
+        }
+        if let ProposalAction::Rename { name } = &action {
+            length_check(name, 3, 64, true)?;
+        }
+
+        let group = &mut ctx.accounts.group;
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.group = group.key();
+        proposal.id = group.proposals;
+        proposal.proposer = ctx.accounts.signer.key();
+        proposal.payer = ctx.accounts.payer.key();
+        proposal.action = action;
+        proposal.deadline = deadline;
+        group.proposals += 1;
+
+        Ok(())
+    }
+
+    pub fn cast_vote(ctx: Context<CastVote>, approve: bool) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        if Clock::get()?.unix_timestamp >= proposal.deadline {
+            return Err(error!(ErrorCode::VotingClosed))
+        }
+
+        let vote = &mut ctx.accounts.vote;
+        vote.proposal = proposal.key();
+        vote.voter = ctx.accounts.signer.key();
+        vote.payer = ctx.accounts.payer.key();
+        vote.approve = approve;
+
+        let tally = if approve { &mut proposal.votes_for } else { &mut proposal.votes_against };
+        *tally = tally.checked_add(1).ok_or(ErrorCode::VoteOverflow)?;
+        proposal.open_votes = proposal.open_votes.checked_add(1).ok_or(ErrorCode::VoteOverflow)?;
+
+        Ok(())
+    }
+
+    pub fn execute_proposal<'info>(ctx: Context<'_, '_, '_, 'info, ExecuteProposal<'info>>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        if Clock::get()?.unix_timestamp < proposal.deadline {
+            return Err(error!(ErrorCode::VotingOpen))
+        }
+
+        let group = &mut ctx.accounts.group;
+        let votes = proposal.votes_for as u32 + proposal.votes_against as u32;
+        if votes * 100 < group.members as u32 * QUORUM_PERCENT || proposal.votes_for <= proposal.votes_against {
+            return Err(error!(ErrorCode::ProposalRejected))
+        }
+
+        match proposal.action.clone() {
+            ProposalAction::Rename { name } => rename_group(group, name)?,
+            ProposalAction::ChangeAdmin { new_admin } => group.pending_admin = new_admin,
+            ProposalAction::Dissolve => dissolve_group(group, ctx.remaining_accounts)?,
+        }
+        proposal.executed = true;
+
+        Ok(())
+    }
+
+    pub fn close_vote(ctx: Context<CloseVote>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        proposal_settled_check(proposal)?;
+        proposal.open_votes -= 1;
+        Ok(())
+    }
+
+    pub fn close_proposal(ctx: Context<CloseProposal>) -> Result<()> {
+        proposal_settled_check(&ctx.accounts.proposal)
+    }
+
+    // hands the admin role over to the multisig, which acts through `execute_multisig_transaction`
+    pub fn create_multisig(ctx: Context<CreateMultisig>, owners: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        owners_check(&owners, threshold)?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.group = ctx.accounts.group.key();
+        multisig.owners = owners;
+        multisig.threshold = threshold;
+        multisig.bump = *ctx.bumps.get("multisig").unwrap();
+
+        ctx.accounts.group.admin = multisig.key();
+        ctx.accounts.group.pending_admin = Pubkey::default();
+
+        Ok(())
+    }
+
+    pub fn modify_multisig(ctx: Context<ModifyMultisig>, owners: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        owners_check(&owners, threshold)?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.owners = owners;
+        multisig.threshold = threshold;
+        // pending transactions were approved by the old owners
+        multisig.owners_version += 1;
+
+        Ok(())
+    }
+
+    pub fn create_multisig_transaction(ctx: Context<CreateMultisigTransaction>, instruction_accounts: Vec<TransactionAccount>, data: Vec<u8>) -> Result<()> {
+        if instruction_accounts.len() > MAX_MULTISIG_ACCOUNTS || data.len() > MAX_MULTISIG_DATA {
+            return Err(error!(ErrorCode::IncorrectField))
+        }
+
+        let multisig = &mut ctx.accounts.multisig;
+        let owner = owner_position(multisig, &ctx.accounts.signer.key())?;
+        let transaction = &mut ctx.accounts.transaction;
+        transaction.multisig = multisig.key();
+        transaction.id = multisig.transactions;
+        transaction.owners_version = multisig.owners_version;
+        transaction.payer = ctx.accounts.payer.key();
+        transaction.accounts = instruction_accounts;
+        transaction.data = data;
+        transaction.approvals = vec![false; multisig.owners.len()];
+        transaction.approvals[owner] = true;
+        multisig.transactions += 1;
+
+        Ok(())
+    }
+
+    pub fn approve_multisig_transaction(ctx: Context<ApproveMultisigTransaction>) -> Result<()> {
+        let owner = owner_position(&ctx.accounts.multisig, &ctx.accounts.signer.key())?;
+        ctx.accounts.transaction.approvals[owner] = true;
+        Ok(())
+    }
+
+    // the stored instruction is invoked on this program with the multisig signing as admin
+    pub fn execute_multisig_transaction(ctx: Context<ExecuteMultisigTransaction>) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let transaction = &mut ctx.accounts.transaction;
+
+        let approvals = transaction.approvals.iter().filter(|approved| **approved).count();
+        if approvals < multisig.threshold as usize {
+            return Err(error!(ErrorCode::NotEnoughApprovals))
+        }
+        transaction.executed = true;
+
+        let instruction = Instruction {
+            program_id: crate::ID,
+            accounts: transaction.accounts.iter().map(|account| AccountMeta {
+                pubkey: account.pubkey,
+                is_signer: account.is_signer || account.pubkey == multisig.key(),
+                is_writable: account.is_writable,
+            }).collect(),
+            data: transaction.data.clone(),
+        };
+        invoke_signed(&instruction, ctx.remaining_accounts, &[&[&multisig.group.to_bytes()[..32], MULTISIG_PDA_SEED, &[multisig.bump]]])?;
+
+        Ok(())
+    }
+
+    pub fn close_multisig_transaction(_ctx: Context<CloseMultisigTransaction>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn migrate_group(ctx: Context<MigrateGroup>) -> Result<()> {
+        let legacy_group: LegacyGroup = read_legacy(&ctx.accounts.group, LEGACY_GROUP_LEN, Group::discriminator())?;
+        let legacy_invitation: LegacyInvitation = read_legacy(&ctx.accounts.invitation, LEGACY_INVITATION_LEN, Invitation::discriminator())?;
+        let group_key = ctx.accounts.group.key();
+        let signer = ctx.accounts.signer.key();
+
+        if legacy_group.admin != signer || legacy_invitation.recipient != signer {
+            return Err(error!(ErrorCode::WrongPrivileges))
+        }
+        if legacy_invitation.group_key != group_key {
+            return Err(error!(ErrorCode::InvitationMismatch))
+        }
+        if legacy_invitation.sender != ctx.accounts.invitation_payer.key() {
+            return Err(error!(ErrorCode::PayerMismatch))
+        }
+
+        // members are counted again as their invitations get migrated with `migrate_invitation`
+        let group = Group {
+            creator: legacy_group.creator,
+            admin: legacy_group.admin,
+            open_invites: legacy_group.open_invites,
+            members: 1,
+            name: legacy_group.name,
+            db_type: DbType::Extension(legacy_invitation.db_type),
+            db_type_version: DB_TYPE_VERSION,
+            group_id: legacy_invitation.group_id.clone(),
+            visibility: Visibility::Private,
+            category: 0,
+            description: "".to_string(),
+            tags: vec![],
+            pending_admin: Pubkey::default(),
+            dissolved: false,
+            avatar_hash: "".to_string(),
+            topic: "".to_string(),
+            announcement_only: false,
+            proposals: 0,
+            invite_only_friends: false,
+            // legacy groups were created by their payer
+            payer: legacy_group.creator,
+            roster_pages: 1,
+        };
+        grow_account(&ctx.accounts.group, Group::LEN, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+        write_account(&ctx.accounts.group, &group)?;
+
+        migrate_legacy_invitation(&ctx.accounts.invitation, &ctx.accounts.invitation_payer, legacy_invitation)?;
+
+        add_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, signer, group_key, ctx.accounts.payer.key())?;
+        add_to_roster(&mut ctx.accounts.roster_page, 0, group_key, signer, ctx.accounts.payer.key())?;
+
         Ok(())
     }
 
-    pub fn close(_ctx: Context<Close>) -> Result<()> {
+    pub fn migrate_invitation(ctx: Context<MigrateInvitation>, recipient: Pubkey) -> Result<()> {
+        let legacy_invitation: LegacyInvitation = read_legacy(&ctx.accounts.invitation, LEGACY_INVITATION_LEN, Invitation::discriminator())?;
+        let group = &mut ctx.accounts.group;
+
+        if legacy_invitation.group_key != group.key() || legacy_invitation.recipient != recipient {
+            return Err(error!(ErrorCode::InvitationMismatch))
+        }
+        if legacy_invitation.sender != ctx.accounts.invitation_payer.key() {
+            return Err(error!(ErrorCode::PayerMismatch))
+        }
+
+        migrate_legacy_invitation(&ctx.accounts.invitation, &ctx.accounts.invitation_payer, legacy_invitation)?;
+
+        add_member(group, &mut ctx.accounts.roster_page, recipient, ctx.accounts.payer.key())?;
+        add_membership(&mut ctx.accounts.membership_index, &mut ctx.accounts.membership_page, recipient, group.key(), ctx.accounts.payer.key())?;
+
         Ok(())
     }
 }
@@ -122,14 +864,38 @@ pub struct Create<'info> {
         bump
     )]
     pub invitation: Account<'info, Invitation>,
-    pub signer: Signer<'info>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipIndex::LEN,
+        seeds = [&signer.key.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipPage::LEN,
+        seeds = [&signer.key.to_bytes()[..32], &membership_page_number(membership_index.groups).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        init,
+        payer = payer,
+        space = RosterPage::LEN,
+        seeds = [&group.key().to_bytes()[..32], &0u32.to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(group_id: String, recipient: Pubkey)]
+#[instruction(recipient: Pubkey)]
 pub struct Invite<'info> {
     #[account(
         init,
@@ -142,13 +908,38 @@ pub struct Invite<'info> {
     #[account(
         mut,
         constraint = (invitation.recipient == signer.key() && group.open_invites) ||
-                     signer.key() == group.admin @ ErrorCode::WrongPrivileges
+                     signer.key() == group.admin @ ErrorCode::WrongPrivileges,
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
     )]
     pub group: Account<'info, Group>,
     #[account(
         constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
     )]
     pub invitation: Account<'info, Invitation>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipIndex::LEN,
+        seeds = [&recipient.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipPage::LEN,
+        seeds = [&recipient.to_bytes()[..32], &membership_page_number(membership_index.groups).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = RosterPage::LEN,
+        seeds = [&group.key().to_bytes()[..32], &roster_page_number(group.members).to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
     pub signer: Signer<'info>,
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -156,138 +947,1551 @@ pub struct Invite<'info> {
 }
 
 #[derive(Accounts)]
-pub struct ModifySuccessor<'info> {
+pub struct InviteSponsored<'info> {
+    /// CHECK: created by the instruction once the sponsor funded it
+    #[account(mut)]
+    pub new_invitation: UncheckedAccount<'info>,
     #[account(
         mut,
-        has_one = admin @ ErrorCode::WrongPrivileges
+        constraint = (invitation.recipient == signer.key() && group.open_invites) ||
+                     signer.key() == group.admin @ ErrorCode::WrongPrivileges,
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
     )]
     pub group: Account<'info, Group>,
     #[account(
-        constraint = successor.group_key == group.key() @ ErrorCode::InvitationMismatch
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
     )]
-    pub successor: Account<'info, Invitation>,
-    pub admin: Signer<'info>,
+    pub invitation: Account<'info, Invitation>,
+    /// CHECK: loaded by the instruction, or created once the sponsor funded it
+    #[account(mut)]
+    pub membership_index: UncheckedAccount<'info>,
+    /// CHECK: loaded by the instruction, or created once the sponsor funded it
+    #[account(mut)]
+    pub membership_page: UncheckedAccount<'info>,
+    /// CHECK: loaded by the instruction, or created once the sponsor funded it
+    #[account(mut)]
+    pub roster_page: UncheckedAccount<'info>,
+    pub signer: Signer<'info>,
+    /// CHECK: checked by the sponsorship program
+    pub sponsor: UncheckedAccount<'info>,
+    /// CHECK: checked by the sponsorship program
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: checked by the sponsorship program
+    #[account(mut)]
+    pub quota: UncheckedAccount<'info>,
+    /// CHECK: instructions sysvar, checked by the sponsorship program
+    pub instructions: UncheckedAccount<'info>,
+    pub sponsorship_program: Program<'info, Sponsorship>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ModifyParameter<'info> {
+#[instruction(code_hash: [u8; 32])]
+pub struct CreateInviteLink<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = InviteLink::LEN,
+        seeds = [&group.key().to_bytes()[..32], &code_hash, INVITE_LINK_PDA_SEED],
+        bump
+    )]
+    pub invite_link: Account<'info, InviteLink>,
     #[account(
-        mut,
         has_one = admin @ ErrorCode::WrongPrivileges
     )]
     pub group: Account<'info, Group>,
     pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Leave<'info> {
+pub struct JoinWithLink<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Invitation::LEN,
+        seeds = [&signer.key.to_bytes()[..32], &group.key().to_bytes()[..32], INVITE_PDA_SEED],
+        bump
+    )]
+    pub new_invitation: Account<'info, Invitation>,
     #[account(
         mut,
-        constraint = (signer.key() != group.admin && signer.key() == invitation.recipient) ||
-                     (signer.key() == group.admin && signer.key() != invitation.recipient) @ ErrorCode::WrongPrivileges
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
     )]
     pub group: Account<'info, Group>,
     #[account(
         mut,
-        close = invitation_sender, 
-        constraint = invitation_sender.key() == invitation.sender @ ErrorCode::PayerMismatch,
+        constraint = invite_link.group == group.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invite_link: Account<'info, InviteLink>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipIndex::LEN,
+        seeds = [&signer.key.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipPage::LEN,
+        seeds = [&signer.key.to_bytes()[..32], &membership_page_number(membership_index.groups).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = RosterPage::LEN,
+        seeds = [&group.key().to_bytes()[..32], &roster_page_number(group.members).to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeliverKey<'info> {
+    #[account(
+        constraint = (invitation.recipient == signer.key() && group.open_invites) ||
+                     signer.key() == group.admin @ ErrorCode::WrongPrivileges,
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
         constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
     )]
     pub invitation: Account<'info, Invitation>,
+    #[account(
+        mut,
+        constraint = group.key() == recipient_invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = recipient_invitation.encryption_key.is_empty() @ ErrorCode::KeyAlreadyDelivered
+    )]
+    pub recipient_invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseInviteLink<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = invite_link.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = invite_link.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub invite_link: Account<'info, InviteLink>,
+    #[account(
+        has_one = admin @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RequestToJoin<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = JoinRequest::LEN,
+        seeds = [&signer.key.to_bytes()[..32], &group.key().to_bytes()[..32], JOIN_REQUEST_PDA_SEED],
+        bump
+    )]
+    pub join_request: Account<'info, JoinRequest>,
+    #[account(
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
+    )]
+    pub group: Account<'info, Group>,
     pub signer: Signer<'info>,
     #[account(mut)]
-    pub invitation_sender: SystemAccount<'info>,
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct AdminLeave<'info> {
+pub struct ApproveJoin<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Invitation::LEN,
+        seeds = [&join_request.requester.to_bytes()[..32], &group.key().to_bytes()[..32], INVITE_PDA_SEED],
+        bump
+    )]
+    pub new_invitation: Account<'info, Invitation>,
     #[account(
         mut,
-        constraint = signer.key() == group.admin &&
-                     signer.key() == invitation.recipient @ ErrorCode::WrongPrivileges
+        has_one = admin @ ErrorCode::WrongPrivileges,
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
     )]
     pub group: Account<'info, Group>,
     #[account(
         mut,
-        close = invitation_sender, 
-        constraint = invitation_sender.key() == invitation.sender @ ErrorCode::PayerMismatch,
-        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
+        close = join_request_payer,
+        constraint = join_request.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = join_request.payer == join_request_payer.key() @ ErrorCode::PayerMismatch
     )]
-    pub invitation: Account<'info, Invitation>,
+    pub join_request: Account<'info, JoinRequest>,
     #[account(
-        constraint = successor.group_key == group.key() &&
-                     successor.recipient != invitation.recipient @ ErrorCode::InvitationMismatch
+        init_if_needed,
+        payer = payer,
+        space = MembershipIndex::LEN,
+        seeds = [&join_request.requester.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
     )]
-    pub successor: Account<'info, Invitation>,
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipPage::LEN,
+        seeds = [&join_request.requester.to_bytes()[..32], &membership_page_number(membership_index.groups).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = RosterPage::LEN,
+        seeds = [&group.key().to_bytes()[..32], &roster_page_number(group.members).to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub join_request_payer: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RejectJoin<'info> {
+    #[account(
+        has_one = admin @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        mut,
+        close = join_request_payer,
+        constraint = join_request.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = join_request.payer == join_request_payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub join_request: Account<'info, JoinRequest>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub join_request_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelJoinRequest<'info> {
+    /// CHECK: may already be closed, checked by `group_closed_check`
+    pub group: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = join_request_payer,
+        constraint = join_request.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = join_request.requester == signer.key() || group_closed_check(&group) @ ErrorCode::WrongPrivileges,
+        constraint = join_request.payer == join_request_payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub join_request: Account<'info, JoinRequest>,
     pub signer: Signer<'info>,
     #[account(mut)]
-    pub invitation_sender: SystemAccount<'info>,
+    pub join_request_payer: SystemAccount<'info>,
 }
 
 #[derive(Accounts)]
-pub struct Close<'info> {
+pub struct ModifySuccessor<'info> {
     #[account(
         mut,
-        close = creator, 
-        constraint = signer.key() == group.admin @ ErrorCode::WrongPrivileges,
-        constraint = group.members == 1 @ ErrorCode::NotEmpty,
-        constraint = group.creator == creator.key() @ ErrorCode::PayerMismatch
+        has_one = admin @ ErrorCode::WrongPrivileges
     )]
     pub group: Account<'info, Group>,
+    #[account(
+        seeds = [&successor.recipient.to_bytes()[..32], &group.key().to_bytes()[..32], INVITE_PDA_SEED],
+        bump,
+        constraint = successor.group_key == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = successor.recipient != admin.key() @ ErrorCode::SelfSuccession,
+        constraint = !successor.encryption_key.is_empty() @ ErrorCode::SuccessorNotMember
+    )]
+    pub successor: Account<'info, Invitation>,
+    #[account(
+        constraint = roster_page.group == group.key() @ ErrorCode::IndexMismatch,
+        constraint = roster_page.members.contains(&successor.recipient) @ ErrorCode::SuccessorNotMember
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    pub admin: Signer<'info>,
+}
+
+// a successor proposed by vote was never checked, so the membership checks of `modify_successor` are repeated here
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
     #[account(
         mut,
-        close = invitation_sender, 
-        constraint = invitation_sender.key() == invitation.sender @ ErrorCode::PayerMismatch,
-        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
+        constraint = group.pending_admin == signer.key() @ ErrorCode::NotProposedAdmin
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        seeds = [&signer.key().to_bytes()[..32], &group.key().to_bytes()[..32], INVITE_PDA_SEED],
+        bump,
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = !invitation.encryption_key.is_empty() @ ErrorCode::SuccessorNotMember
     )]
     pub invitation: Account<'info, Invitation>,
+    #[account(
+        constraint = roster_page.group == group.key() @ ErrorCode::IndexMismatch,
+        constraint = roster_page.members.contains(&signer.key()) @ ErrorCode::SuccessorNotMember
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
     pub signer: Signer<'info>,
-    #[account(mut)]
-    pub creator: SystemAccount<'info>,
-    #[account(mut)]
-    pub invitation_sender: SystemAccount<'info>,
 }
 
-#[account]
-pub struct Group {
-    pub creator: Pubkey,
-    pub admin: Pubkey,
-    pub open_invites: bool,
-    pub members: u8,
-    pub name: String,
+#[derive(Accounts)]
+pub struct ModifyParameter<'info> {
+    #[account(
+        mut,
+        has_one = admin @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    pub admin: Signer<'info>,
 }
 
-impl Group {
-    const LEN: usize = DISCRIMINATOR_LENGTH
-    + PUBKEY_LENGTH
-    + PUBKEY_LENGTH
-    + BOOL_LENGTH
-    + U8_LENGTH
-    + STRING_LENGTH_PREFIX + STRING_LENGTH_NAME;
+#[derive(Accounts)]
+pub struct SetRole<'info> {
+    #[account(
+        has_one = admin @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        mut,
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub admin: Signer<'info>,
 }
 
-#[account]
-pub struct Invitation {
-    pub sender: Pubkey,
-    pub group_key: Pubkey,
-    pub recipient: Pubkey,
-    pub group_id: String,
-    pub encryption_key: String,
-    pub db_type: u8,
+#[derive(Accounts)]
+pub struct ModifyProfile<'info> {
+    #[account(
+        mut,
+        constraint = signer.key() == group.admin || invitation.role == Role::Moderator @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
 }
 
-impl Invitation {
-    const LEN: usize = DISCRIMINATOR_LENGTH
-    + PUBKEY_LENGTH
-    + PUBKEY_LENGTH
-    + PUBKEY_LENGTH
-    + STRING_LENGTH_PREFIX +STRING_LENGTH_GROUP_ID
-    + STRING_LENGTH_PREFIX + STRING_LENGTH_ENCRYPTION_KEY
-    + DB_TYPE_LENGTH;
+#[derive(Accounts)]
+pub struct Mute<'info> {
+    #[account(
+        constraint = signer.key() == group.admin || invitation.role == Role::Moderator @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    // moderators can only be muted by the admin
+    #[account(
+        mut,
+        seeds = [&member_invitation.recipient.to_bytes()[..32], &group.key().to_bytes()[..32], INVITE_PDA_SEED],
+        bump,
+        constraint = group.key() == member_invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = member_invitation.recipient != group.admin &&
+                     member_invitation.recipient != signer.key() @ ErrorCode::WrongPrivileges,
+        constraint = member_invitation.role != Role::Moderator || signer.key() == group.admin @ ErrorCode::WrongPrivileges
+    )]
+    pub member_invitation: Account<'info, Invitation>,
+    #[account(
+        constraint = roster_page.group == group.key() @ ErrorCode::IndexMismatch,
+        constraint = roster_page.members.contains(&member_invitation.recipient) @ ErrorCode::NotMember
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    pub signer: Signer<'info>,
 }
 
-#[error_code]
-pub enum ErrorCode {
+#[derive(Accounts)]
+pub struct Pin<'info> {
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Pins::LEN,
+        seeds = [&group.key().to_bytes()[..32], PINS_PDA_SEED],
+        bump
+    )]
+    pub pins: Account<'info, Pins>,
+    #[account(
+        constraint = signer.key() == group.admin || invitation.role == Role::Moderator @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Unpin<'info> {
+    #[account(
+        mut,
+        seeds = [&group.key().to_bytes()[..32], PINS_PDA_SEED],
+        bump
+    )]
+    pub pins: Account<'info, Pins>,
+    #[account(
+        constraint = signer.key() == group.admin || invitation.role == Role::Moderator @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(channel_id: u16)]
+pub struct CreateChannel<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Channel::LEN,
+        seeds = [&group.key().to_bytes()[..32], &channel_id.to_le_bytes(), CHANNEL_PDA_SEED],
+        bump
+    )]
+    pub channel: Account<'info, Channel>,
+    #[account(
+        constraint = signer.key() == group.admin || invitation.role == Role::Moderator @ ErrorCode::WrongPrivileges,
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyChannel<'info> {
+    #[account(
+        mut,
+        constraint = channel.group == group.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub channel: Account<'info, Channel>,
+    #[account(
+        constraint = signer.key() == group.admin || invitation.role == Role::Moderator @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteChannel<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = channel.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = channel.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub channel: Account<'info, Channel>,
+    #[account(
+        constraint = signer.key() == group.admin || invitation.role == Role::Moderator @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Leave<'info> {
+    #[account(
+        mut,
+        constraint = group.dissolved ||
+                     (signer.key() != group.admin && signer.key() == invitation.recipient) ||
+                     (signer.key() == group.admin && signer.key() != invitation.recipient) @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        mut,
+        close = invitation_payer, 
+        constraint = invitation_payer.key() == invitation.payer @ ErrorCode::PayerMismatch,
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        mut,
+        constraint = membership_page.user == invitation.recipient @ ErrorCode::IndexMismatch
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], &membership_page_number(membership_index.groups.saturating_sub(1)).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_last_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        constraint = roster_page.group == group.key() @ ErrorCode::IndexMismatch
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    #[account(
+        mut,
+        seeds = [&group.key().to_bytes()[..32], &roster_page_number(group.members.saturating_sub(1)).to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_last_page: Box<Account<'info, RosterPage>>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub invitation_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveDeactivatedMember<'info> {
+    #[account(mut)]
+    pub group: Account<'info, Group>,
+    #[account(
+        mut,
+        close = invitation_payer, 
+        constraint = invitation_payer.key() == invitation.payer @ ErrorCode::PayerMismatch,
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    /// CHECK: validated by `verify_deactivated_profile`
+    #[account(
+        constraint = verify_deactivated_profile(&user, &closed_profile, &invitation.recipient) == Some(true) @ ErrorCode::NotDeactivated
+    )]
+    pub user: UncheckedAccount<'info>,
+    /// CHECK: validated by `verify_deactivated_profile`
+    pub closed_profile: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        mut,
+        constraint = membership_page.user == invitation.recipient @ ErrorCode::IndexMismatch
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], &membership_page_number(membership_index.groups.saturating_sub(1)).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_last_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        constraint = roster_page.group == group.key() @ ErrorCode::IndexMismatch
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    #[account(
+        mut,
+        seeds = [&group.key().to_bytes()[..32], &roster_page_number(group.members.saturating_sub(1)).to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_last_page: Box<Account<'info, RosterPage>>,
+    #[account(mut)]
+    pub invitation_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminLeave<'info> {
+    #[account(
+        mut,
+        constraint = signer.key() == group.admin &&
+                     signer.key() == invitation.recipient @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        mut,
+        close = invitation_payer, 
+        constraint = invitation_payer.key() == invitation.payer @ ErrorCode::PayerMismatch,
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    #[account(
+        constraint = successor.group_key == group.key() &&
+                     successor.recipient != invitation.recipient @ ErrorCode::InvitationMismatch,
+        constraint = successor.recipient == new_admin.key() @ ErrorCode::WrongPrivileges
+    )]
+    pub successor: Account<'info, Invitation>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        mut,
+        constraint = membership_page.user == invitation.recipient @ ErrorCode::IndexMismatch
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], &membership_page_number(membership_index.groups.saturating_sub(1)).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_last_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        constraint = roster_page.group == group.key() @ ErrorCode::IndexMismatch
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    #[account(
+        mut,
+        seeds = [&group.key().to_bytes()[..32], &roster_page_number(group.members.saturating_sub(1)).to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_last_page: Box<Account<'info, RosterPage>>,
+    pub signer: Signer<'info>,
+    pub new_admin: Signer<'info>,
+    #[account(mut)]
+    pub invitation_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferPayer<'info> {
+    #[account(
+        mut,
+        has_one = admin @ ErrorCode::WrongPrivileges,
+        constraint = group.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub group: Account<'info, Group>,
+    pub admin: Signer<'info>,
+    pub payer: Signer<'info>,
+    pub new_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferInvitationPayer<'info> {
+    #[account(
+        mut,
+        constraint = invitation.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub payer: Signer<'info>,
+    pub new_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Close<'info> {
+    #[account(
+        mut,
+        close = group_payer, 
+        constraint = signer.key() == group.admin @ ErrorCode::WrongPrivileges,
+        constraint = group.members == 1 @ ErrorCode::NotEmpty,
+        constraint = group.payer == group_payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        mut,
+        close = invitation_payer, 
+        constraint = invitation_payer.key() == invitation.payer @ ErrorCode::PayerMismatch,
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        mut,
+        constraint = membership_page.user == invitation.recipient @ ErrorCode::IndexMismatch
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], &membership_page_number(membership_index.groups.saturating_sub(1)).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_last_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        close = roster_page_payer,
+        seeds = [&group.key().to_bytes()[..32], &0u32.to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump,
+        constraint = roster_page.payer == roster_page_payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub group_payer: SystemAccount<'info>,
+    #[account(mut)]
+    pub invitation_payer: SystemAccount<'info>,
+    #[account(mut)]
+    pub roster_page_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Dissolve<'info> {
+    #[account(
+        mut,
+        has_one = admin @ ErrorCode::WrongPrivileges,
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
+    )]
+    pub group: Account<'info, Group>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDissolved<'info> {
+    #[account(
+        mut,
+        close = group_payer,
+        constraint = group.dissolved @ ErrorCode::NotDissolved,
+        constraint = group.members == 0 @ ErrorCode::NotEmpty,
+        constraint = group.payer == group_payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        mut,
+        close = roster_page_payer,
+        seeds = [&group.key().to_bytes()[..32], &0u32.to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump,
+        constraint = roster_page.payer == roster_page_payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    #[account(mut)]
+    pub group_payer: SystemAccount<'info>,
+    #[account(mut)]
+    pub roster_page_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseMembershipPage<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = membership_page.groups.is_empty() @ ErrorCode::PageNotEmpty,
+        constraint = membership_page.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub membership_page: Account<'info, MembershipPage>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseMembershipIndex<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = membership_index.groups == 0 @ ErrorCode::PageNotEmpty,
+        constraint = membership_index.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub membership_index: Account<'info, MembershipIndex>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDirectoryPage<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = directory_page.groups.is_empty() @ ErrorCode::PageNotEmpty,
+        constraint = directory_page.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub directory_page: Account<'info, DirectoryPage>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDirectory<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = directory.groups == 0 @ ErrorCode::PageNotEmpty,
+        constraint = directory.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub directory: Account<'info, Directory>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePins<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = pins.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = pins.items.is_empty() || group_closed_check(&group) @ ErrorCode::PageNotEmpty,
+        constraint = pins.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub pins: Account<'info, Pins>,
+    /// CHECK: may already be closed, checked by `group_closed_check`
+    pub group: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReapInviteLink<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = invite_link.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = invite_link.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub invite_link: Account<'info, InviteLink>,
+    /// CHECK: may already be closed, checked by `group_closed_check`
+    #[account(
+        constraint = group_closed_check(&group) @ ErrorCode::GroupStillActive
+    )]
+    pub group: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReapChannel<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = channel.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = channel.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub channel: Account<'info, Channel>,
+    /// CHECK: may already be closed, checked by `group_closed_check`
+    #[account(
+        constraint = group_closed_check(&group) @ ErrorCode::GroupStillActive
+    )]
+    pub group: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReapInvitation<'info> {
+    #[account(
+        mut,
+        close = invitation_payer,
+        constraint = invitation_payer.key() == invitation.payer @ ErrorCode::PayerMismatch,
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    #[account(
+        mut,
+        constraint = group.dissolved @ ErrorCode::GroupStillActive
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        mut,
+        constraint = membership_page.user == invitation.recipient @ ErrorCode::IndexMismatch
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        mut,
+        seeds = [&invitation.recipient.to_bytes()[..32], &membership_page_number(membership_index.groups.saturating_sub(1)).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_last_page: Box<Account<'info, MembershipPage>>,
+    #[account(mut)]
+    pub invitation_payer: SystemAccount<'info>,
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReapLegacyInvitation<'info> {
+    /// CHECK: legacy layout, decoded by the instruction
+    #[account(
+        mut,
+        owner = crate::ID
+    )]
+    pub invitation: UncheckedAccount<'info>,
+    /// CHECK: the group may no longer exist, it is inspected by the instruction
+    pub group: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub invitation_payer: SystemAccount<'info>,
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Proposal::LEN,
+        seeds = [&group.key().to_bytes()[..32], &group.proposals.to_le_bytes(), PROPOSAL_PDA_SEED],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    #[account(
+        mut,
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CastVote<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Vote::LEN,
+        seeds = [&proposal.key().to_bytes()[..32], &invitation.key().to_bytes()[..32], VOTE_PDA_SEED],
+        bump
+    )]
+    pub vote: Account<'info, Vote>,
+    #[account(
+        mut,
+        constraint = proposal.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = !proposal.executed @ ErrorCode::ProposalExecuted
+    )]
+    pub proposal: Account<'info, Proposal>,
+    #[account(
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
+    )]
+    pub group: Account<'info, Group>,
+    #[account(
+        constraint = group.key() == invitation.group_key @ ErrorCode::InvitationMismatch,
+        constraint = invitation.recipient == signer.key() @ ErrorCode::InvitationMismatch
+    )]
+    pub invitation: Account<'info, Invitation>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(
+        mut,
+        constraint = proposal.group == group.key() @ ErrorCode::InvitationMismatch,
+        constraint = !proposal.executed @ ErrorCode::ProposalExecuted
+    )]
+    pub proposal: Account<'info, Proposal>,
+    #[account(
+        mut,
+        constraint = !group.dissolved @ ErrorCode::GroupDissolved
+    )]
+    pub group: Account<'info, Group>,
+}
+
+#[derive(Accounts)]
+pub struct CloseVote<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = vote.proposal == proposal.key() @ ErrorCode::InvitationMismatch,
+        constraint = vote.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub vote: Account<'info, Vote>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseProposal<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = proposal.open_votes == 0 @ ErrorCode::VotesOutstanding,
+        constraint = proposal.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub proposal: Account<'info, Proposal>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMultisig<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Multisig::LEN,
+        seeds = [&group.key().to_bytes()[..32], MULTISIG_PDA_SEED],
+        bump
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(
+        mut,
+        has_one = admin @ ErrorCode::WrongPrivileges
+    )]
+    pub group: Account<'info, Group>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// only reachable through `execute_multisig_transaction`
+#[derive(Accounts)]
+pub struct ModifyMultisig<'info> {
+    #[account(
+        mut,
+        constraint = multisig.to_account_info().is_signer @ ErrorCode::WrongPrivileges
+    )]
+    pub multisig: Account<'info, Multisig>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMultisigTransaction<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = MultisigTransaction::LEN,
+        seeds = [&multisig.key().to_bytes()[..32], &multisig.transactions.to_le_bytes(), MULTISIG_TRANSACTION_PDA_SEED],
+        bump
+    )]
+    pub transaction: Account<'info, MultisigTransaction>,
+    #[account(mut)]
+    pub multisig: Account<'info, Multisig>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveMultisigTransaction<'info> {
+    #[account(
+        mut,
+        constraint = transaction.multisig == multisig.key() @ ErrorCode::InvitationMismatch,
+        constraint = transaction.owners_version == multisig.owners_version @ ErrorCode::OwnersChanged,
+        constraint = !transaction.executed @ ErrorCode::TransactionExecuted
+    )]
+    pub transaction: Account<'info, MultisigTransaction>,
+    pub multisig: Account<'info, Multisig>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteMultisigTransaction<'info> {
+    #[account(
+        mut,
+        constraint = transaction.multisig == multisig.key() @ ErrorCode::InvitationMismatch,
+        constraint = transaction.owners_version == multisig.owners_version @ ErrorCode::OwnersChanged,
+        constraint = !transaction.executed @ ErrorCode::TransactionExecuted
+    )]
+    pub transaction: Account<'info, MultisigTransaction>,
+    // not mutable here, the invoked instruction may change it
+    pub multisig: Account<'info, Multisig>,
+}
+
+#[derive(Accounts)]
+pub struct CloseMultisigTransaction<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = transaction.executed || transaction.owners_version != multisig.owners_version @ ErrorCode::NotEnoughApprovals,
+        constraint = transaction.multisig == multisig.key() @ ErrorCode::InvitationMismatch,
+        constraint = transaction.payer == payer.key() @ ErrorCode::PayerMismatch
+    )]
+    pub transaction: Account<'info, MultisigTransaction>,
+    pub multisig: Account<'info, Multisig>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateGroup<'info> {
+    /// CHECK: legacy layout, parsed and rewritten by the instruction
+    #[account(
+        mut,
+        owner = crate::ID
+    )]
+    pub group: UncheckedAccount<'info>,
+    /// CHECK: legacy layout of the admin invitation, parsed and rewritten by the instruction
+    #[account(
+        mut,
+        owner = crate::ID
+    )]
+    pub invitation: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipIndex::LEN,
+        seeds = [&signer.key.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipPage::LEN,
+        seeds = [&signer.key.to_bytes()[..32], &membership_page_number(membership_index.groups).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        init,
+        payer = payer,
+        space = RosterPage::LEN,
+        seeds = [&group.key().to_bytes()[..32], &0u32.to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub invitation_payer: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(recipient: Pubkey)]
+pub struct MigrateInvitation<'info> {
+    #[account(mut)]
+    pub group: Account<'info, Group>,
+    /// CHECK: legacy layout, parsed and rewritten by the instruction
+    #[account(
+        mut,
+        owner = crate::ID
+    )]
+    pub invitation: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipIndex::LEN,
+        seeds = [&recipient.to_bytes()[..32], MEMBERSHIP_INDEX_PDA_SEED],
+        bump
+    )]
+    pub membership_index: Box<Account<'info, MembershipIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MembershipPage::LEN,
+        seeds = [&recipient.to_bytes()[..32], &membership_page_number(membership_index.groups).to_le_bytes(), MEMBERSHIP_PAGE_PDA_SEED],
+        bump
+    )]
+    pub membership_page: Box<Account<'info, MembershipPage>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = RosterPage::LEN,
+        seeds = [&group.key().to_bytes()[..32], &roster_page_number(group.members).to_le_bytes(), ROSTER_PAGE_PDA_SEED],
+        bump
+    )]
+    pub roster_page: Box<Account<'info, RosterPage>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub invitation_payer: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct Group {
+    pub creator: Pubkey,
+    pub admin: Pubkey,
+    pub open_invites: bool,
+    pub members: u8,
+    pub name: String,
+    pub db_type: DbType,
+    pub db_type_version: u8,
+    pub group_id: String,
+    pub visibility: Visibility,
+    pub category: u8,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub pending_admin: Pubkey,
+    pub dissolved: bool,
+    pub avatar_hash: String,
+    pub topic: String,
+    pub announcement_only: bool,
+    pub proposals: u32,
+    pub payer: Pubkey,
+    pub invite_only_friends: bool,
+    pub roster_pages: u8,
+}
+
+impl Group {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + BOOL_LENGTH
+    + U8_LENGTH
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_NAME
+    + DB_TYPE_LENGTH
+    + U8_LENGTH
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_GROUP_ID
+    + VISIBILITY_LENGTH
+    + U8_LENGTH
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_DESCRIPTION
+    + VEC_LENGTH_PREFIX + (STRING_LENGTH_PREFIX + STRING_LENGTH_TAG) * MAX_TAGS
+    + PUBKEY_LENGTH
+    + BOOL_LENGTH
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_AVATAR_HASH
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_TOPIC
+    + BOOL_LENGTH
+    + U32_LENGTH
+    + PUBKEY_LENGTH
+    + BOOL_LENGTH
+    + U8_LENGTH;
+}
+
+#[account]
+pub struct Invitation {
+    pub sender: Pubkey,
+    pub group_key: Pubkey,
+    pub recipient: Pubkey,
+    pub encryption_key: String,
+    pub role: Role,
+    pub muted_until: i64,
+    pub payer: Pubkey,
+}
+
+impl Invitation {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_ENCRYPTION_KEY
+    + ROLE_LENGTH
+    + I64_LENGTH
+    + PUBKEY_LENGTH;
+}
+
+#[account]
+pub struct Directory {
+    pub category: u8,
+    pub groups: u32,
+    pub payer: Pubkey,
+}
+
+impl Directory {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + U8_LENGTH
+    + U32_LENGTH
+    + PUBKEY_LENGTH;
+}
+
+#[account]
+pub struct DirectoryPage {
+    pub category: u8,
+    pub page: u32,
+    pub groups: Vec<Pubkey>,
+    pub payer: Pubkey,
+}
+
+impl DirectoryPage {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + U8_LENGTH
+    + U32_LENGTH
+    + VEC_LENGTH_PREFIX + PUBKEY_LENGTH * DIRECTORY_PAGE_CAPACITY
+    + PUBKEY_LENGTH;
+}
+
+#[account]
+pub struct InviteLink {
+    pub group: Pubkey,
+    pub payer: Pubkey,
+    pub code_hash: [u8; 32],
+    pub max_uses: u16,
+    pub uses: u16,
+    pub expires_at: i64,
+}
+
+impl InviteLink {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + HASH_LENGTH
+    + U16_LENGTH
+    + U16_LENGTH
+    + I64_LENGTH;
+}
+
+#[account]
+pub struct JoinRequest {
+    pub group: Pubkey,
+    pub requester: Pubkey,
+    pub payer: Pubkey,
+}
+
+impl JoinRequest {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH;
+}
+
+// layouts of the accounts created before group_id was moved from Invitation to Group
+#[derive(AnchorDeserialize)]
+struct LegacyGroup {
+    creator: Pubkey,
+    admin: Pubkey,
+    open_invites: bool,
+    #[allow(dead_code)]
+    members: u8,
+    name: String,
+}
+
+#[derive(AnchorDeserialize)]
+struct LegacyInvitation {
+    sender: Pubkey,
+    group_key: Pubkey,
+    recipient: Pubkey,
+    group_id: String,
+    encryption_key: String,
+    db_type: u8,
+}
+
+#[event]
+pub struct GroupCreated {
+    pub group: Pubkey,
+    pub admin: Pubkey,
+    pub db_type: DbType,
+    pub db_type_version: u8,
+}
+
+#[event]
+pub struct MemberInvited {
+    pub group: Pubkey,
+    pub recipient: Pubkey,
+    pub db_type: DbType,
+    pub db_type_version: u8,
+}
+
+#[event]
+pub struct PinAdded {
+    pub group: Pubkey,
+    pub content_hash: String,
+    pub pinned_by: Pubkey,
+}
+
+#[event]
+pub struct PinRemoved {
+    pub group: Pubkey,
+    pub content_hash: String,
+    pub unpinned_by: Pubkey,
+}
+
+#[account]
+pub struct MembershipIndex {
+    pub user: Pubkey,
+    pub groups: u32,
+    pub payer: Pubkey,
+}
+
+impl MembershipIndex {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + U32_LENGTH
+    + PUBKEY_LENGTH;
+}
+
+#[account]
+pub struct MembershipPage {
+    pub user: Pubkey,
+    pub page: u32,
+    pub groups: Vec<Pubkey>,
+    pub payer: Pubkey,
+}
+
+impl MembershipPage {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + U32_LENGTH
+    + VEC_LENGTH_PREFIX + PUBKEY_LENGTH * MEMBERSHIP_PAGE_CAPACITY
+    + PUBKEY_LENGTH;
+}
+
+#[account]
+pub struct RosterPage {
+    pub group: Pubkey,
+    pub page: u32,
+    pub members: Vec<Pubkey>,
+    pub payer: Pubkey,
+}
+
+impl RosterPage {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + U32_LENGTH
+    + VEC_LENGTH_PREFIX + PUBKEY_LENGTH * ROSTER_PAGE_CAPACITY
+    + PUBKEY_LENGTH;
+}
+
+#[account]
+pub struct Pins {
+    pub group: Pubkey,
+    pub items: Vec<String>,
+    pub payer: Pubkey,
+}
+
+impl Pins {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + VEC_LENGTH_PREFIX + (STRING_LENGTH_PREFIX + STRING_LENGTH_CONTENT_HASH) * MAX_PINS
+    + PUBKEY_LENGTH;
+}
+
+// like `muted_until`, `min_role` is enforced by clients and relays against the member's `Invitation`
+#[account]
+pub struct Channel {
+    pub group: Pubkey,
+    pub channel_id: u16,
+    pub name: String,
+    pub encryption_key: String,
+    pub min_role: Role,
+    pub payer: Pubkey,
+}
+
+impl Channel {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + U16_LENGTH
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_CHANNEL_NAME
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_ENCRYPTION_KEY
+    + ROLE_LENGTH
+    + PUBKEY_LENGTH;
+}
+
+#[account]
+pub struct Proposal {
+    pub group: Pubkey,
+    pub id: u32,
+    pub proposer: Pubkey,
+    pub payer: Pubkey,
+    pub action: ProposalAction,
+    pub deadline: i64,
+    pub votes_for: u16,
+    pub votes_against: u16,
+    pub open_votes: u16,
+    pub executed: bool,
+}
+
+impl Proposal {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + U32_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + PROPOSAL_ACTION_LENGTH
+    + I64_LENGTH
+    + U16_LENGTH
+    + U16_LENGTH
+    + U16_LENGTH
+    + BOOL_LENGTH;
+}
+
+#[account]
+pub struct Vote {
+    pub proposal: Pubkey,
+    pub voter: Pubkey,
+    pub payer: Pubkey,
+    pub approve: bool,
+}
+
+impl Vote {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + BOOL_LENGTH;
+}
+
+#[account]
+pub struct Multisig {
+    pub group: Pubkey,
+    pub owners: Vec<Pubkey>,
+    pub threshold: u8,
+    pub bump: u8,
+    pub transactions: u32,
+    pub owners_version: u32,
+}
+
+impl Multisig {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + VEC_LENGTH_PREFIX + PUBKEY_LENGTH * MAX_MULTISIG_OWNERS
+    + U8_LENGTH
+    + U8_LENGTH
+    + U32_LENGTH
+    + U32_LENGTH;
+}
+
+#[account]
+pub struct MultisigTransaction {
+    pub multisig: Pubkey,
+    pub id: u32,
+    pub owners_version: u32,
+    pub payer: Pubkey,
+    pub accounts: Vec<TransactionAccount>,
+    pub data: Vec<u8>,
+    pub approvals: Vec<bool>,
+    pub executed: bool,
+}
+
+impl MultisigTransaction {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + U32_LENGTH
+    + U32_LENGTH
+    + PUBKEY_LENGTH
+    + VEC_LENGTH_PREFIX + TRANSACTION_ACCOUNT_LENGTH * MAX_MULTISIG_ACCOUNTS
+    + VEC_LENGTH_PREFIX + MAX_MULTISIG_DATA
+    + VEC_LENGTH_PREFIX + BOOL_LENGTH * MAX_MULTISIG_OWNERS
+    + BOOL_LENGTH;
+}
+
+#[error_code]
+pub enum ErrorCode {
     #[msg("User cannot perform this action")]
     WrongPrivileges,
     #[msg("Invite does not match Group ID")]
@@ -300,6 +2504,514 @@ pub enum ErrorCode {
     IncorrectField,
     #[msg("Parameters order mismatch")]
     InputError,
+    #[msg("Index does not match the provided accounts")]
+    IndexMismatch,
+    #[msg("Database type is not supported")]
+    InvalidDbType,
+    #[msg("Group hash does not match group id and creator")]
+    GroupHashMismatch,
+    #[msg("Account is not in the legacy layout")]
+    NotLegacyAccount,
+    #[msg("Invite code does not match the link")]
+    InvalidInviteCode,
+    #[msg("Invite link expired")]
+    InviteLinkExpired,
+    #[msg("Invite link has no uses left")]
+    InviteLinkExhausted,
+    #[msg("Encryption key already delivered")]
+    KeyAlreadyDelivered,
+    #[msg("User is not the proposed admin")]
+    NotProposedAdmin,
+    #[msg("Admin cannot be their own successor")]
+    SelfSuccession,
+    #[msg("Successor is not an active member of the group")]
+    SuccessorNotMember,
+    #[msg("Group has been dissolved")]
+    GroupDissolved,
+    #[msg("Group has not been dissolved")]
+    NotDissolved,
+    #[msg("Group is still active")]
+    GroupStillActive,
+    #[msg("Item is already pinned")]
+    AlreadyPinned,
+    #[msg("Pinned items limit reached")]
+    TooManyPins,
+    #[msg("Item is not pinned")]
+    NotPinned,
+    #[msg("Deadline must be in the future")]
+    InvalidDeadline,
+    #[msg("Voting period is over")]
+    VotingClosed,
+    #[msg("Voting period is not over")]
+    VotingOpen,
+    #[msg("Proposal did not reach quorum or majority")]
+    ProposalRejected,
+    #[msg("Proposal was already executed")]
+    ProposalExecuted,
+    #[msg("Proposal still has open votes")]
+    VotesOutstanding,
+    #[msg("Threshold must be between one and the number of owners")]
+    InvalidThreshold,
+    #[msg("Transaction does not have enough approvals")]
+    NotEnoughApprovals,
+    #[msg("Transaction was already executed")]
+    TransactionExecuted,
+    #[msg("Multisig owners changed since the transaction was created")]
+    OwnersChanged,
+    #[msg("User profile is not deactivated")]
+    NotDeactivated,
+    #[msg("Inviter and recipient are not friends")]
+    NotFriends,
+    #[msg("Proposal can still be executed")]
+    ProposalPending,
+    #[msg("Multisig owners must be unique")]
+    DuplicateOwner,
+    #[msg("Account still has entries")]
+    PageNotEmpty,
+    #[msg("User is not a member of the group")]
+    NotMember,
+    #[msg("Proposal cannot take more votes")]
+    VoteOverflow,
+}
+
+// the group address is derived from sha256(group_id || creator) so it cannot be squatted by someone else
+fn group_hash_check(group_hash: &[u8; 32], group_id: &str, creator: &Pubkey) -> Result<()> {
+    if hashv(&[group_id.as_bytes(), creator.as_ref()]).to_bytes() != *group_hash {
+        return Err(error!(ErrorCode::GroupHashMismatch))
+    }
+
+    Ok(())
+}
+
+fn db_type_check(db_type: DbType) -> Result<()> {
+    match db_type {
+        DbType::Unknown => Err(error!(ErrorCode::InvalidDbType)),
+        _ => Ok(())
+    }
+}
+
+fn read_legacy<T: AnchorDeserialize>(account: &AccountInfo, legacy_len: usize, discriminator: [u8; 8]) -> Result<T> {
+    let data = account.try_borrow_data()?;
+    if data.len() != legacy_len || data[..8] != discriminator {
+        return Err(error!(ErrorCode::NotLegacyAccount))
+    }
+
+    match T::deserialize(&mut &data[8..]) {
+        Ok(legacy) => Ok(legacy),
+        Err(_) => Err(error!(ErrorCode::NotLegacyAccount))
+    }
+}
+
+fn write_account<T: AccountSerialize>(account: &AccountInfo, value: &T) -> Result<()> {
+    let mut data = account.try_borrow_mut_data()?;
+    value.try_serialize(&mut &mut data[..])
+}
+
+fn grow_account<'info>(account: &AccountInfo<'info>, new_len: usize, payer: &AccountInfo<'info>, system_program: &AccountInfo<'info>) -> Result<()> {
+    let missing_rent = Rent::get()?.minimum_balance(new_len).saturating_sub(account.lamports());
+    if missing_rent > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+        system_program::transfer(CpiContext::new(system_program.clone(), cpi_accounts), missing_rent)?;
+    }
+    account.realloc(new_len, false)?;
+
+    Ok(())
+}
+
+fn shrink_account(account: &AccountInfo, new_len: usize, refund: &AccountInfo) -> Result<()> {
+    account.realloc(new_len, false)?;
+    let excess_rent = account.lamports().saturating_sub(Rent::get()?.minimum_balance(new_len));
+    **account.try_borrow_mut_lamports()? -= excess_rent;
+    **refund.try_borrow_mut_lamports()? += excess_rent;
+
+    Ok(())
+}
+
+// rewrites a legacy invitation in the current layout and refunds the freed rent to its payer
+fn migrate_legacy_invitation(account: &AccountInfo, payer: &AccountInfo, legacy: LegacyInvitation) -> Result<()> {
+    let invitation = Invitation {
+        sender: legacy.sender,
+        group_key: legacy.group_key,
+        recipient: legacy.recipient,
+        encryption_key: legacy.encryption_key,
+        role: Role::Member,
+        muted_until: 0,
+        payer: legacy.sender,
+    };
+    write_account(account, &invitation)?;
+    shrink_account(account, Invitation::LEN, payer)
+}
+
+// legacy accounts cannot be loaded as typed accounts, so they are closed by hand
+fn close_legacy_account(account: &AccountInfo, destination: &AccountInfo) -> Result<()> {
+    let lamports = account.lamports();
+    **account.try_borrow_mut_lamports()? -= lamports;
+    **destination.try_borrow_mut_lamports()? += lamports;
+    account.try_borrow_mut_data()?.fill(0);
+
+    Ok(())
+}
+
+fn load_roster_pages<'info>(accounts: &[AccountInfo<'info>], group: &Account<'info, Group>) -> Result<(Account<'info, RosterPage>, Account<'info, RosterPage>)> {
+    if accounts.len() < 2 {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+
+    let roster_page: Account<RosterPage> = Account::try_from(&accounts[0])?;
+    let roster_last_page: Account<RosterPage> = Account::try_from(&accounts[1])?;
+    let last_page_number = roster_page_number(group.members.saturating_sub(1));
+    let (last_page_key, _) = Pubkey::find_program_address(&[&group.key().to_bytes()[..32], &last_page_number.to_le_bytes(), ROSTER_PAGE_PDA_SEED], &crate::ID);
+
+    if roster_page.group != group.key() || roster_last_page.key() != last_page_key {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+    if !accounts[0].is_writable || !accounts[1].is_writable {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+
+    Ok((roster_page, roster_last_page))
+}
+
+fn membership_page_number(position: u32) -> u32 {
+    position / MEMBERSHIP_PAGE_CAPACITY as u32
+}
+
+fn roster_page_number(position: u8) -> u32 {
+    position as u32 / ROSTER_PAGE_CAPACITY as u32
+}
+
+fn directory_page_number(position: u32) -> u32 {
+    position / DIRECTORY_PAGE_CAPACITY as u32
+}
+
+fn add_to_directory(directory: &mut Directory, page: &mut DirectoryPage, category: u8, group: Pubkey) -> Result<()> {
+    directory.category = category;
+    page.category = category;
+    page.page = directory_page_number(directory.groups);
+    push_entry(&mut page.groups, DIRECTORY_PAGE_CAPACITY, group)?;
+    directory.groups += 1;
+    Ok(())
+}
+
+fn remove_from_directory(directory: &mut Directory, page: &mut DirectoryPage, last_page: &mut DirectoryPage, group: Pubkey) -> Result<()> {
+    swap_remove_entry(page.page, &mut page.groups, last_page.page, &mut last_page.groups, group)?;
+    directory.groups -= 1;
+    Ok(())
+}
+
+// public groups pass their directory and its last page as remaining accounts, both are created on first use
+fn list_group<'info>(accounts: &[AccountInfo<'info>], category: u8, group: Pubkey, payer: &AccountInfo<'info>, system_program: &AccountInfo<'info>) -> Result<()> {
+    if accounts.len() < 2 {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+
+    let mut directory: Account<Directory> = load_or_create(&accounts[0], &[&[category][..], DIRECTORY_PDA_SEED], Directory::LEN, payer, system_program)?;
+    let page_number = directory_page_number(directory.groups).to_le_bytes();
+    let mut directory_page: Account<DirectoryPage> = load_or_create(&accounts[1], &[&[category][..], &page_number, DIRECTORY_PAGE_PDA_SEED], DirectoryPage::LEN, payer, system_program)?;
+
+    add_to_directory(&mut directory, &mut directory_page, category, group)?;
+    record_payer(&mut directory.payer, payer.key());
+    record_payer(&mut directory_page.payer, payer.key());
+    directory.exit(&crate::ID)?;
+    directory_page.exit(&crate::ID)
+}
+
+// a listed group passes its directory, the page it is listed on and the last directory page as remaining accounts
+fn delist_group<'info>(accounts: &[AccountInfo<'info>], group: &Account<'info, Group>) -> Result<()> {
+    if accounts.len() < 3 {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+
+    let mut directory: Account<Directory> = Account::try_from(&accounts[0])?;
+    let mut directory_page: Account<DirectoryPage> = Account::try_from(&accounts[1])?;
+    let mut directory_last_page: Account<DirectoryPage> = Account::try_from(&accounts[2])?;
+    let (directory_key, _) = Pubkey::find_program_address(&[&[group.category][..], DIRECTORY_PDA_SEED], &crate::ID);
+    let last_page_number = directory_page_number(directory.groups.saturating_sub(1));
+    let (last_page_key, _) = Pubkey::find_program_address(&[&[group.category][..], &last_page_number.to_le_bytes(), DIRECTORY_PAGE_PDA_SEED], &crate::ID);
+
+    if directory.key() != directory_key || directory_page.category != group.category || directory_last_page.key() != last_page_key {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+    if accounts[..3].iter().any(|account| !account.is_writable) {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+
+    remove_from_directory(&mut directory, &mut directory_page, &mut directory_last_page, group.key())?;
+    directory.exit(&crate::ID)?;
+    directory_page.exit(&crate::ID)?;
+    directory_last_page.exit(&crate::ID)
+}
+
+// every roster page past the first is followed by the payer it refunds
+fn close_roster_pages<'info>(accounts: &[AccountInfo<'info>], group: &Account<'info, Group>) -> Result<()> {
+    if accounts.len() != group.roster_pages.saturating_sub(1) as usize * 2 {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+
+    for (index, pair) in accounts.chunks(2).enumerate() {
+        let (account, payer) = (&pair[0], &pair[1]);
+        let page_number = index as u32 + 1;
+        let (page_key, _) = Pubkey::find_program_address(&[&group.key().to_bytes()[..32], &page_number.to_le_bytes(), ROSTER_PAGE_PDA_SEED], &crate::ID);
+        if account.key() != page_key || !account.is_writable {
+            return Err(error!(ErrorCode::IndexMismatch))
+        }
+
+        let page: Account<RosterPage> = Account::try_from(account)?;
+        if page.payer != payer.key() || !payer.is_writable {
+            return Err(error!(ErrorCode::PayerMismatch))
+        }
+        page.close(payer.clone())?;
+    }
+
+    Ok(())
+}
+
+// what `init_if_needed` does for a PDA of this program that is only known at runtime
+fn load_or_create<'info, T: AccountSerialize + AccountDeserialize + Owner + Clone>(account: &AccountInfo<'info>, seeds: &[&[u8]], space: usize, payer: &AccountInfo<'info>, system_program: &AccountInfo<'info>) -> Result<Account<'info, T>> {
+    let (address, bump) = Pubkey::find_program_address(seeds, &crate::ID);
+    if account.key() != address || !account.is_writable {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+    if account.owner == &crate::ID {
+        return Account::try_from(account)
+    }
+
+    let bump = [bump];
+    let signer_seeds = [seeds, &[&bump[..]]].concat();
+    let missing_rent = Rent::get()?.minimum_balance(space).saturating_sub(account.lamports());
+    if missing_rent > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+        system_program::transfer(CpiContext::new(system_program.clone(), cpi_accounts), missing_rent)?;
+    }
+    let cpi_accounts = system_program::Allocate {
+        account_to_allocate: account.clone(),
+    };
+    system_program::allocate(CpiContext::new_with_signer(system_program.clone(), cpi_accounts, &[&signer_seeds]), space as u64)?;
+    let cpi_accounts = system_program::Assign {
+        account_to_assign: account.clone(),
+    };
+    system_program::assign(CpiContext::new_with_signer(system_program.clone(), cpi_accounts, &[&signer_seeds]), &crate::ID)?;
+
+    Account::try_from_unchecked(account)
+}
+
+// funds and creates a PDA of this program through the sponsorship program, failing if it already exists
+fn create_sponsored<'info, T: AccountSerialize + AccountDeserialize + Owner + Clone>(accounts: &InviteSponsored<'info>, account: &AccountInfo<'info>, seeds: &[&[u8]], space: usize) -> Result<Account<'info, T>> {
+    let fund_rent_accounts = sponsorship::cpi::accounts::FundRent {
+        sponsor: accounts.sponsor.to_account_info(),
+        vault: accounts.vault.to_account_info(),
+        quota: accounts.quota.to_account_info(),
+        user: accounts.signer.to_account_info(),
+        account: account.clone(),
+        instructions: accounts.instructions.to_account_info(),
+        system_program: accounts.system_program.to_account_info(),
+    };
+    create_sponsored_account(&accounts.sponsorship_program, fund_rent_accounts, seeds, space, &crate::ID)?;
+    Account::try_from_unchecked(account)
+}
+
+// what `load_or_create` does with the rent coming from the sponsor's vault
+fn load_or_create_sponsored<'info, T: AccountSerialize + AccountDeserialize + Owner + Clone>(accounts: &InviteSponsored<'info>, account: &AccountInfo<'info>, seeds: &[&[u8]], space: usize) -> Result<Account<'info, T>> {
+    let (address, _) = Pubkey::find_program_address(seeds, &crate::ID);
+    if account.key() != address {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+    if account.owner == &crate::ID {
+        return Account::try_from(account)
+    }
+
+    create_sponsored(accounts, account, seeds, space)
+}
+
+// only `invite_only_friends` groups require an accepted friend request between inviter and recipient
+fn invite_only_friends_check(group: &Group, remaining_accounts: &[AccountInfo], inviter: &Pubkey, recipient: &Pubkey) -> Result<()> {
+    if !group.invite_only_friends {
+        return Ok(())
+    }
+
+    match remaining_accounts.first() {
+        Some(request) if verify_friendship(request, inviter, recipient) => Ok(()),
+        _ => Err(error!(ErrorCode::NotFriends))
+    }
+}
+
+// `sender` and `payer` of the invitation are set by the caller, the pages it creates share its payer
+fn invite_member(group: &mut Account<Group>, invitation: &mut Invitation, membership_index: &mut MembershipIndex, membership_page: &mut MembershipPage, roster_page: &mut RosterPage, recipient: Pubkey, encryption_key: String) -> Result<()> {
+    add_member(group, roster_page, recipient, invitation.payer)?;
+    invitation.group_key = group.key();
+    invitation.recipient = recipient;
+
+    length_check(&encryption_key, 64, 64, true)?;
+    invitation.encryption_key = encryption_key;
+
+    add_membership(membership_index, membership_page, recipient, group.key(), invitation.payer)?;
+
+    emit!(MemberInvited {
+        group: group.key(),
+        recipient,
+        db_type: group.db_type,
+        db_type_version: group.db_type_version,
+    });
+
+    Ok(())
+}
+
+fn add_membership(index: &mut MembershipIndex, page: &mut MembershipPage, user: Pubkey, group: Pubkey, payer: Pubkey) -> Result<()> {
+    index.user = user;
+    page.user = user;
+    record_payer(&mut index.payer, payer);
+    record_payer(&mut page.payer, payer);
+    page.page = membership_page_number(index.groups);
+    push_entry(&mut page.groups, MEMBERSHIP_PAGE_CAPACITY, group)?;
+    index.groups += 1;
+    Ok(())
+}
+
+fn remove_membership(index: &mut MembershipIndex, page: &mut MembershipPage, last_page: &mut MembershipPage, group: Pubkey) -> Result<()> {
+    swap_remove_entry(page.page, &mut page.groups, last_page.page, &mut last_page.groups, group)?;
+    index.groups -= 1;
+    Ok(())
+}
+
+// the roster is counted by `group.members`, which callers keep up to date
+fn add_to_roster(page: &mut RosterPage, position: u8, group: Pubkey, member: Pubkey, payer: Pubkey) -> Result<()> {
+    page.group = group;
+    record_payer(&mut page.payer, payer);
+    page.page = roster_page_number(position);
+    push_entry(&mut page.members, ROSTER_PAGE_CAPACITY, member)
+}
+
+// roster pages are never closed before the group, so the group keeps count of them
+fn add_member(group: &mut Account<Group>, page: &mut RosterPage, member: Pubkey, payer: Pubkey) -> Result<()> {
+    add_to_roster(page, group.members, group.key(), member, payer)?;
+    group.roster_pages = group.roster_pages.max(page.page as u8 + 1);
+    group.members += 1;
+    Ok(())
+}
+
+// pages and indexes are shared, the rent goes back to whoever created them
+fn record_payer(recorded: &mut Pubkey, payer: Pubkey) {
+    if *recorded == Pubkey::default() {
+        *recorded = payer;
+    }
+}
+
+fn remove_from_roster(page: &mut RosterPage, last_page: &mut RosterPage, member: Pubkey) -> Result<()> {
+    swap_remove_entry(page.page, &mut page.members, last_page.page, &mut last_page.members, member)
+}
+
+// appends an entry to the last page of a paged list
+fn push_entry(entries: &mut Vec<Pubkey>, capacity: usize, entry: Pubkey) -> Result<()> {
+    if entries.len() >= capacity {
+        return Err(error!(ErrorCode::IndexMismatch))
+    }
+
+    entries.push(entry);
+
+    Ok(())
+}
+
+// removes an entry from a paged list, moving the last entry of the list into the freed slot
+fn swap_remove_entry(page: u32, entries: &mut Vec<Pubkey>, last_page: u32, last_entries: &mut Vec<Pubkey>, entry: Pubkey) -> Result<()> {
+    let position = match entries.iter().position(|key| *key == entry) {
+        Some(position) => position,
+        None => return Err(error!(ErrorCode::IndexMismatch))
+    };
+
+    // the same page can be passed twice, in that case both copies have to end up identical
+    if page == last_page {
+        entries.swap_remove(position);
+        *last_entries = entries.clone();
+    } else {
+        match last_entries.pop() {
+            Some(last) => entries[position] = last,
+            None => return Err(error!(ErrorCode::IndexMismatch))
+        }
+    }
+
+    Ok(())
+}
+
+fn owners_check(owners: &[Pubkey], threshold: u8) -> Result<()> {
+    if owners.is_empty() || owners.len() > MAX_MULTISIG_OWNERS {
+        return Err(error!(ErrorCode::IncorrectField))
+    }
+    if threshold == 0 || threshold as usize > owners.len() {
+        return Err(error!(ErrorCode::InvalidThreshold))
+    }
+    // a repeated owner would count its approval twice
+    if owners.iter().enumerate().any(|(position, owner)| owners[..position].contains(owner)) {
+        return Err(error!(ErrorCode::DuplicateOwner))
+    }
+
+    Ok(())
+}
+
+fn owner_position(multisig: &Multisig, owner: &Pubkey) -> Result<usize> {
+    match multisig.owners.iter().position(|key| key == owner) {
+        Some(position) => Ok(position),
+        None => Err(error!(ErrorCode::WrongPrivileges))
+    }
+}
+
+// shared by the admin instructions and executed proposals
+fn rename_group(group: &mut Group, name: String) -> Result<()> {
+    length_check(&name, 3, 64, true)?;
+    group.name = name;
+
+    Ok(())
+}
+
+fn dissolve_group<'info>(group: &mut Account<'info, Group>, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+    group.dissolved = true;
+    if group.visibility == Visibility::Public {
+        delist_group(remaining_accounts, group)?;
+    }
+
+    Ok(())
+}
+
+// true once the group was dissolved, or closed altogether
+fn group_closed_check(group: &AccountInfo) -> bool {
+    if group.owner != &crate::ID || group.lamports() == 0 {
+        return true
+    }
+
+    match Account::<Group>::try_from(group) {
+        Ok(group) => group.dissolved,
+        Err(_) => false
+    }
+}
+
+// quorum depends on the current member count, so only a lost majority settles a proposal before the execution window ends
+fn proposal_settled_check(proposal: &Proposal) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    if now < proposal.deadline {
+        return Err(error!(ErrorCode::VotingOpen))
+    }
+    if !proposal.executed && proposal.votes_for > proposal.votes_against && now < proposal.deadline + EXECUTION_WINDOW_SECONDS {
+        return Err(error!(ErrorCode::ProposalPending))
+    }
+
+    Ok(())
+}
+
+fn tags_check(tags: &[String]) -> Result<()> {
+    if tags.len() > MAX_TAGS {
+        return Err(error!(ErrorCode::IncorrectField))
+    }
+
+    for tag in tags {
+        length_check(tag, 1, 16, true)?;
+    }
+
+    Ok(())
 }
 
 fn length_check(field: &String, min_accepted_length: usize, max_accepted_length: usize, is_mandatory: bool) -> Result<()> {
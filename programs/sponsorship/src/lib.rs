@@ -0,0 +1,356 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
+use anchor_lang::system_program;
+
+declare_id!("5TR6V1BD6mt4ZRdPkdZufcfGw6tNA9UyS2mjs9kpVHbL");
+
+const SPONSOR_PDA_SEED: &[u8] = b"sponsor";
+const VAULT_PDA_SEED: &[u8] = b"vault";
+const QUOTA_PDA_SEED: &[u8] = b"quota";
+const MAX_ALLOWED_INSTRUCTIONS: usize = 16;
+const DISCRIMINATOR_LENGTH: usize = 8;
+const PUBKEY_LENGTH: usize = 32;
+const SIGHASH_LENGTH: usize = 8;
+const VEC_LENGTH_PREFIX: usize = 4;
+const U8_LENGTH: usize = 1;
+const U64_LENGTH: usize = 8;
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize, PartialEq)]
+pub struct AllowedInstruction {
+    pub program_id: Pubkey,
+    pub sighash: [u8; 8],
+}
+
+#[program]
+pub mod sponsorship {
+    use super::*;
+
+    pub fn create_sponsor(ctx: Context<CreateSponsor>, allowed_instructions: Vec<AllowedInstruction>) -> Result<()> {
+        allowed_instructions_check(&allowed_instructions)?;
+
+        let sponsor = &mut ctx.accounts.sponsor;
+        sponsor.authority = ctx.accounts.authority.key();
+        sponsor.payer = ctx.accounts.payer.key();
+        sponsor.vault_bump = *ctx.bumps.get("vault").unwrap();
+        sponsor.allowed_instructions = allowed_instructions;
+
+        Ok(())
+    }
+
+    pub fn set_allowed_instructions(ctx: Context<ModifySponsor>, allowed_instructions: Vec<AllowedInstruction>) -> Result<()> {
+        allowed_instructions_check(&allowed_instructions)?;
+
+        let sponsor = &mut ctx.accounts.sponsor;
+        sponsor.allowed_instructions = allowed_instructions;
+
+        Ok(())
+    }
+
+    pub fn transfer_payer(ctx: Context<TransferPayer>) -> Result<()> {
+        let sponsor = &mut ctx.accounts.sponsor;
+        sponsor.payer = ctx.accounts.new_payer.key();
+
+        Ok(())
+    }
+
+    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.depositor.to_account_info(),
+            to: ctx.accounts.vault.to_account_info(),
+        };
+        system_program::transfer(CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts), amount)
+    }
+
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        vault_transfer(&ctx.accounts.sponsor, &ctx.accounts.vault, &ctx.accounts.authority.to_account_info(), &ctx.accounts.system_program, amount)
+    }
+
+    pub fn set_quota(ctx: Context<SetQuota>, user: Pubkey, lamports: u64) -> Result<()> {
+        let quota = &mut ctx.accounts.quota;
+        quota.sponsor = ctx.accounts.sponsor.key();
+        quota.user = user;
+        quota.remaining = lamports;
+        Ok(())
+    }
+
+    // called by the sponsored programs through `create_sponsored_account`, funds the rent of the one account they are about to create
+    pub fn fund_rent(ctx: Context<FundRent>, space: u64) -> Result<()> {
+        let instruction = sponsored_instruction(&ctx.accounts.instructions)?;
+        if !is_allowed(&ctx.accounts.sponsor, &instruction, &ctx.accounts.user.key(), &ctx.accounts.account.key()) {
+            return Err(error!(ErrorCode::InstructionNotAllowed))
+        }
+
+        let amount = Rent::get()?.minimum_balance(space as usize).saturating_sub(ctx.accounts.account.lamports());
+        let quota = &mut ctx.accounts.quota;
+        if amount > quota.remaining {
+            return Err(error!(ErrorCode::QuotaExceeded))
+        }
+
+        quota.remaining -= amount;
+        vault_transfer(&ctx.accounts.sponsor, &ctx.accounts.vault, &ctx.accounts.account.to_account_info(), &ctx.accounts.system_program, amount)
+    }
+
+    pub fn close_quota(_ctx: Context<CloseQuota>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct CreateSponsor<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Sponsor::LEN,
+        seeds = [&authority.key().to_bytes()[..32], SPONSOR_PDA_SEED],
+        bump
+    )]
+    pub sponsor: Account<'info, Sponsor>,
+    #[account(
+        seeds = [&sponsor.key().to_bytes()[..32], VAULT_PDA_SEED],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModifySponsor<'info> {
+    #[account(
+        mut,
+        has_one = authority @ ErrorCode::WrongPrivileges
+    )]
+    pub sponsor: Account<'info, Sponsor>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferPayer<'info> {
+    #[account(
+        mut,
+        has_one = authority @ ErrorCode::WrongPrivileges,
+        has_one = payer @ ErrorCode::PayerMismatch
+    )]
+    pub sponsor: Account<'info, Sponsor>,
+    pub authority: Signer<'info>,
+    pub payer: Signer<'info>,
+    pub new_payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    pub sponsor: Account<'info, Sponsor>,
+    #[account(
+        mut,
+        seeds = [&sponsor.key().to_bytes()[..32], VAULT_PDA_SEED],
+        bump = sponsor.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(
+        has_one = authority @ ErrorCode::WrongPrivileges
+    )]
+    pub sponsor: Account<'info, Sponsor>,
+    #[account(
+        mut,
+        seeds = [&sponsor.key().to_bytes()[..32], VAULT_PDA_SEED],
+        bump = sponsor.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct SetQuota<'info> {
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Quota::LEN,
+        seeds = [&sponsor.key().to_bytes()[..32], &user.to_bytes()[..32], QUOTA_PDA_SEED],
+        bump
+    )]
+    pub quota: Account<'info, Quota>,
+    #[account(
+        has_one = authority @ ErrorCode::WrongPrivileges
+    )]
+    pub sponsor: Account<'info, Sponsor>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundRent<'info> {
+    pub sponsor: Account<'info, Sponsor>,
+    #[account(
+        mut,
+        seeds = [&sponsor.key().to_bytes()[..32], VAULT_PDA_SEED],
+        bump = sponsor.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [&sponsor.key().to_bytes()[..32], &user.key().to_bytes()[..32], QUOTA_PDA_SEED],
+        bump
+    )]
+    pub quota: Account<'info, Quota>,
+    pub user: Signer<'info>,
+    /// CHECK: not created yet, the calling program allocates and assigns it once funded
+    #[account(
+        mut,
+        constraint = account.owner == &System::id() && account.data_is_empty() @ ErrorCode::AccountInUse
+    )]
+    pub account: UncheckedAccount<'info>,
+    /// CHECK: instructions sysvar, used to look at the sponsored instruction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseQuota<'info> {
+    #[account(
+        mut,
+        close = payer,
+        constraint = quota.sponsor == sponsor.key() @ ErrorCode::WrongPrivileges
+    )]
+    pub quota: Account<'info, Quota>,
+    #[account(
+        has_one = authority @ ErrorCode::WrongPrivileges,
+        has_one = payer @ ErrorCode::PayerMismatch
+    )]
+    pub sponsor: Account<'info, Sponsor>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[account]
+pub struct Sponsor {
+    pub authority: Pubkey,
+    pub payer: Pubkey,
+    pub vault_bump: u8,
+    pub allowed_instructions: Vec<AllowedInstruction>,
+}
+
+impl Sponsor {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + U8_LENGTH
+    + VEC_LENGTH_PREFIX + (PUBKEY_LENGTH + SIGHASH_LENGTH) * MAX_ALLOWED_INSTRUCTIONS;
+}
+
+#[account]
+pub struct Quota {
+    pub sponsor: Pubkey,
+    pub user: Pubkey,
+    pub remaining: u64,
+}
+
+impl Quota {
+    const LEN: usize = DISCRIMINATOR_LENGTH
+    + PUBKEY_LENGTH
+    + PUBKEY_LENGTH
+    + U64_LENGTH;
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("User cannot perform this action")]
+    WrongPrivileges,
+    #[msg("Account was not created by provided user")]
+    PayerMismatch,
+    #[msg("Too many allowed instructions")]
+    TooManyInstructions,
+    #[msg("Sponsor quota exceeded")]
+    QuotaExceeded,
+    #[msg("Instruction is not sponsored")]
+    InstructionNotAllowed,
+    #[msg("Account already exists")]
+    AccountInUse,
+    #[msg("Account does not match the provided seeds")]
+    AccountMismatch,
+}
+
+fn allowed_instructions_check(allowed_instructions: &[AllowedInstruction]) -> Result<()> {
+    if allowed_instructions.len() > MAX_ALLOWED_INSTRUCTIONS {
+        return Err(error!(ErrorCode::TooManyInstructions))
+    }
+
+    Ok(())
+}
+
+// `fund_rent` is only reachable through CPI, so the current top-level instruction is the caller's
+fn sponsored_instruction(instructions: &AccountInfo) -> Result<Instruction> {
+    let current = load_current_index_checked(instructions)?;
+    let instruction = load_instruction_at_checked(current as usize, instructions)?;
+    if instruction.program_id == crate::ID {
+        return Err(error!(ErrorCode::InstructionNotAllowed))
+    }
+
+    Ok(instruction)
+}
+
+// the user has to sign an allowed instruction that writes the funded account
+fn is_allowed(sponsor: &Sponsor, instruction: &Instruction, user: &Pubkey, funded: &Pubkey) -> bool {
+    if instruction.data.len() < SIGHASH_LENGTH {
+        return false
+    }
+
+    let allowed = sponsor.allowed_instructions.iter().any(|allowed| {
+        allowed.program_id == instruction.program_id && allowed.sighash[..] == instruction.data[..SIGHASH_LENGTH]
+    });
+    let signed = instruction.accounts.iter().any(|account| account.pubkey == *user && account.is_signer);
+    let funds = instruction.accounts.iter().any(|account| account.pubkey == *funded && account.is_writable);
+
+    allowed && signed && funds
+}
+
+// creates a PDA of the calling program with its rent drawn from the vault, the caller then writes it as its own account
+#[cfg(feature = "cpi")]
+pub fn create_sponsored_account<'info>(sponsorship_program: &AccountInfo<'info>, accounts: cpi::accounts::FundRent<'info>, seeds: &[&[u8]], space: usize, owner: &Pubkey) -> Result<()> {
+    let (address, bump) = Pubkey::find_program_address(seeds, owner);
+    if accounts.account.key() != address {
+        return Err(error!(ErrorCode::AccountMismatch))
+    }
+
+    let account = accounts.account.clone();
+    let system_program = accounts.system_program.clone();
+    cpi::fund_rent(CpiContext::new(sponsorship_program.clone(), accounts), space as u64)?;
+
+    let bump = [bump];
+    let signer_seeds = [seeds, &[&bump[..]]].concat();
+    let cpi_accounts = system_program::Allocate {
+        account_to_allocate: account.clone(),
+    };
+    system_program::allocate(CpiContext::new_with_signer(system_program.clone(), cpi_accounts, &[&signer_seeds]), space as u64)?;
+    let cpi_accounts = system_program::Assign {
+        account_to_assign: account,
+    };
+    system_program::assign(CpiContext::new_with_signer(system_program, cpi_accounts, &[&signer_seeds]), owner)
+}
+
+fn vault_transfer<'info>(sponsor: &Account<'info, Sponsor>, vault: &SystemAccount<'info>, to: &AccountInfo<'info>, system_program: &Program<'info, System>, amount: u64) -> Result<()> {
+    let sponsor_key = sponsor.key();
+    let seeds: &[&[u8]] = &[&sponsor_key.to_bytes()[..32], VAULT_PDA_SEED, &[sponsor.vault_bump]];
+    let signer_seeds = &[seeds];
+    let cpi_accounts = system_program::Transfer {
+        from: vault.to_account_info(),
+        to: to.clone(),
+    };
+    system_program::transfer(CpiContext::new_with_signer(system_program.to_account_info(), cpi_accounts, signer_seeds), amount)
+}
@@ -1,8 +1,12 @@
 use anchor_lang::prelude::*;
+use anchor_lang::{system_program, Discriminator};
+use sponsorship::program::Sponsorship;
+use sponsorship::create_sponsored_account;
 
 declare_id!("3xjXCBLnaC1Vh7VuqA1SK5G1gpktENVBXMbqJtXMTGZT");
 
 const USER_PDA_SEED: &[u8] = b"user";
+const CLOSED_PROFILE_PDA_SEED: &[u8] = b"closedprofile";
 const DISCRIMINATOR_LENGTH: usize = 8;
 const STRING_LENGTH_PREFIX: usize = 4; 
 const STRING_LENGTH_NAME: usize = 32; 
@@ -15,6 +19,21 @@ const STRING_LENGTH_URI: usize = 32;
 const PUBKEY_USER_LENGTH: usize = 32;
 const PUBKEY_PAYER_LENGTH: usize = 32;
 const NUM_LENGTH: usize = 1;
+const BOOL_LENGTH: usize = 1;
+const U32_LENGTH: usize = 4;
+const FRIEND_INDEX_PDA_SEED: &[u8] = b"friendindex";
+const MEMBERSHIP_INDEX_PDA_SEED: &[u8] = b"membership";
+
+// friends and groupchats depend on this program, so their ids are repeated here
+mod friends_program {
+    use super::*;
+    declare_id!("8MnP9XdAKdCvyK6KjRgu9AAGRAb7GmNxSDakqt5z8jTM");
+}
+
+mod groupchats_program {
+    use super::*;
+    declare_id!("EGSLBxSQyf8aYgV8gavHy2qhdwdrCRunap6Tv3c7YJWP");
+}
 
 #[program]
 pub mod users {
@@ -22,25 +41,30 @@ pub mod users {
 
     pub fn create(ctx: Context<Create>, name: String, photo_hash: String, status: String) -> Result<()> {
         let user = &mut ctx.accounts.user;
-        
-        // this function do a check for the length of the field and has following parameter (field, min_length_accepted, max_length_accepted, is_mandatory)
-        length_check(&name, 3, 32, true)?;
-        user.name = name;
-
         user.signer = ctx.accounts.signer.key();
         user.payer = ctx.accounts.payer.key();
+        init_user(user, name, photo_hash, status)
+    }
 
-        length_check(&photo_hash, 64, 64, false)?;
-        user.photo_hash = photo_hash;
-
-        length_check(&status, 3, 128, false)?;
-        user.status = status; 
-
-        user.banner_image_hash = "".to_string();
-        user.extra_1 = "".to_string();
-        user.extra_2 = "".to_string();
-
-        Ok(())
+    // the rent comes from the sponsor's vault, which also gets it back when the profile is closed
+    pub fn create_sponsored(ctx: Context<CreateSponsored>, name: String, photo_hash: String, status: String) -> Result<()> {
+        let signer = ctx.accounts.signer.key();
+        let accounts = sponsorship::cpi::accounts::FundRent {
+            sponsor: ctx.accounts.sponsor.to_account_info(),
+            vault: ctx.accounts.vault.to_account_info(),
+            quota: ctx.accounts.quota.to_account_info(),
+            user: ctx.accounts.signer.to_account_info(),
+            account: ctx.accounts.user.to_account_info(),
+            instructions: ctx.accounts.instructions.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        };
+        create_sponsored_account(&ctx.accounts.sponsorship_program, accounts, &[&signer.to_bytes()[..32], USER_PDA_SEED], User::LEN, &crate::ID)?;
+
+        let mut user: Account<User> = Account::try_from_unchecked(&ctx.accounts.user)?;
+        user.signer = signer;
+        user.payer = ctx.accounts.vault.key();
+        init_user(&mut user, name, photo_hash, status)?;
+        user.exit(&crate::ID)
     }
 
     pub fn set_name(ctx: Context<Modify>, name: String) -> Result<()> {
@@ -97,7 +121,39 @@ pub mod users {
         Ok(())
     }
 
-    pub fn close(_ctx: Context<Close>) -> Result<()> {
+    // friends and groupchats can tear down the accounts naming a closing user before `close`
+    pub fn deactivate(ctx: Context<Modify>) -> Result<()> {
+        let user = &mut ctx.accounts.user;
+        user.closing = true;
+
+        Ok(())
+    }
+
+    pub fn transfer_payer(ctx: Context<TransferPayer>) -> Result<()> {
+        let user = &mut ctx.accounts.user;
+        user.payer = ctx.accounts.new_payer.key();
+
+        Ok(())
+    }
+
+    // leaves a marker behind so friendships and invitations the indexes never counted, like legacy ones, can still be torn down
+    pub fn close(ctx: Context<Close>) -> Result<()> {
+        mark_closed(&ctx.accounts.user.to_account_info(), &ctx.accounts.closed_profile, &ctx.accounts.signer.key(), &ctx.accounts.system_program)
+    }
+
+    // profiles created before `closing` existed are one byte short, anyone can grow them
+    pub fn migrate_profile(ctx: Context<MigrateProfile>) -> Result<()> {
+        let user = &ctx.accounts.user;
+        let missing_rent = Rent::get()?.minimum_balance(User::LEN).saturating_sub(user.lamports());
+        if missing_rent > 0 {
+            let cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.payer.to_account_info(),
+                to: user.to_account_info(),
+            };
+            system_program::transfer(CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts), missing_rent)?;
+        }
+        user.realloc(User::LEN, true)?;
+
         Ok(())
     }
 }
@@ -119,16 +175,53 @@ pub struct Create<'info> {
 }
 
 #[derive(Accounts)]
-pub struct Modify<'info> {
+pub struct CreateSponsored<'info> {
+    /// CHECK: created by the instruction once the sponsor funded it
     #[account(
         mut,
         seeds = [&signer.key().to_bytes()[..32], USER_PDA_SEED],
         bump
     )]
-    pub user: Account<'info, User>,
+    pub user: UncheckedAccount<'info>,
     pub signer: Signer<'info>,
+    /// CHECK: checked by the sponsorship program
+    pub sponsor: UncheckedAccount<'info>,
+    /// CHECK: checked by the sponsorship program
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: checked by the sponsorship program
     #[account(mut)]
+    pub quota: UncheckedAccount<'info>,
+    /// CHECK: instructions sysvar, checked by the sponsorship program
+    pub instructions: UncheckedAccount<'info>,
+    pub sponsorship_program: Program<'info, Sponsorship>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Modify<'info> {
+    #[account(
+        mut,
+        seeds = [&signer.key().to_bytes()[..32], USER_PDA_SEED],
+        bump,
+        constraint = !user.closing @ ErrorCode::ProfileClosing,
+    )]
+    pub user: Account<'info, User>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferPayer<'info> {
+    #[account(
+        mut,
+        seeds = [&signer.key().to_bytes()[..32], USER_PDA_SEED],
+        bump,
+        constraint = user.payer.key() == payer.key() @ ErrorCode::PayerMismatch,
+    )]
+    pub user: Account<'info, User>,
+    pub signer: Signer<'info>,
     pub payer: Signer<'info>,
+    pub new_payer: SystemAccount<'info>
 }
 
 #[derive(Accounts)]
@@ -138,11 +231,44 @@ pub struct Close<'info> {
         close = payer,
         constraint = signer.key() == user.signer.key() @ ErrorCode::WrongPrivileges,
         constraint = user.payer.key() == payer.key() @ ErrorCode::PayerMismatch,
+        constraint = user.closing @ ErrorCode::NotDeactivated,
     )]
     pub user: Account<'info, User>,
     pub signer: Signer<'info>,
     #[account(mut)]
-    pub payer: SystemAccount<'info>
+    pub payer: SystemAccount<'info>,
+    /// CHECK: validated by `index_count`
+    #[account(
+        constraint = matches!(index_count(&friend_index, &signer.key(), FRIEND_INDEX_PDA_SEED, &friends_program::ID), Some(0)) @ ErrorCode::CleanupPending
+    )]
+    pub friend_index: UncheckedAccount<'info>,
+    /// CHECK: validated by `index_count`
+    #[account(
+        constraint = matches!(index_count(&membership_index, &signer.key(), MEMBERSHIP_INDEX_PDA_SEED, &groupchats_program::ID), Some(0)) @ ErrorCode::CleanupPending
+    )]
+    pub membership_index: UncheckedAccount<'info>,
+    /// CHECK: created by `mark_closed` unless an earlier profile left it
+    #[account(
+        mut,
+        seeds = [&signer.key().to_bytes()[..32], CLOSED_PROFILE_PDA_SEED],
+        bump
+    )]
+    pub closed_profile: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateProfile<'info> {
+    /// CHECK: legacy layout, grown by the instruction
+    #[account(
+        mut,
+        owner = crate::ID,
+        constraint = legacy_profile_check(&user) @ ErrorCode::NotLegacyProfile
+    )]
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[account]
@@ -155,6 +281,12 @@ pub struct User {
     pub banner_image_hash: String,
     pub extra_1: String,
     pub extra_2: String,
+    pub closing: bool,
+}
+
+#[account]
+pub struct ClosedProfile {
+    pub signer: Pubkey,
 }
 
 #[error_code]
@@ -167,6 +299,14 @@ pub enum ErrorCode {
     IncorrectField,
     #[msg("Parameters order mismatch")]
     InputError,
+    #[msg("User profile is being closed")]
+    ProfileClosing,
+    #[msg("User profile must be deactivated first")]
+    NotDeactivated,
+    #[msg("Friends and groups have to be cleaned up first")]
+    CleanupPending,
+    #[msg("Account is not a profile in the legacy layout")]
+    NotLegacyProfile,
 }
 
 impl User {
@@ -178,7 +318,58 @@ impl User {
     + STRING_LENGTH_PREFIX + STRING_LENGTH_STATUS
     + STRING_LENGTH_PREFIX + STRING_LENGTH_BANNER_IMAGE_HASH
     + STRING_LENGTH_PREFIX + STRING_LENGTH_EXTRA_1
-    + STRING_LENGTH_PREFIX + STRING_LENGTH_EXTRA_2;
+    + STRING_LENGTH_PREFIX + STRING_LENGTH_EXTRA_2
+    + BOOL_LENGTH;
+}
+
+impl ClosedProfile {
+    const LEN: usize = DISCRIMINATOR_LENGTH + PUBKEY_USER_LENGTH;
+}
+
+// returns the profile of `user` if `profile` is its PDA and owned by this program, `closing` is left to the caller
+pub fn verify_user_profile(profile: &AccountInfo, user: &Pubkey) -> Option<User> {
+    let (address, _) = Pubkey::find_program_address(&[&user.to_bytes()[..32], USER_PDA_SEED], &ID);
+    if profile.key() != address || *profile.owner != ID {
+        return None
+    }
+
+    let data = profile.try_borrow_data().ok()?;
+    User::try_deserialize(&mut &data[..]).ok()
+}
+
+// whether `user` is leaving: its profile is being closed, or is gone and `closed_profile` marks it as closed
+// None when the accounts are not the PDAs of `user`
+pub fn verify_deactivated_profile(profile: &AccountInfo, closed_profile: &AccountInfo, user: &Pubkey) -> Option<bool> {
+    let (closed_address, _) = Pubkey::find_program_address(&[&user.to_bytes()[..32], CLOSED_PROFILE_PDA_SEED], &ID);
+    if closed_profile.key() != closed_address {
+        return None
+    }
+    if *profile.owner != ID {
+        let (address, _) = Pubkey::find_program_address(&[&user.to_bytes()[..32], USER_PDA_SEED], &ID);
+        return (profile.key() == address).then(|| *closed_profile.owner == ID)
+    }
+
+    verify_user_profile(profile, user).map(|profile| profile.closing)
+}
+
+// fills a new profile, `signer` and `payer` are set by the caller
+fn init_user(user: &mut User, name: String, photo_hash: String, status: String) -> Result<()> {
+    // this function do a check for the length of the field and has following parameter (field, min_length_accepted, max_length_accepted, is_mandatory)
+    length_check(&name, 3, 32, true)?;
+    user.name = name;
+
+    length_check(&photo_hash, 64, 64, false)?;
+    user.photo_hash = photo_hash;
+
+    length_check(&status, 3, 128, false)?;
+    user.status = status;
+
+    user.banner_image_hash = "".to_string();
+    user.extra_1 = "".to_string();
+    user.extra_2 = "".to_string();
+    user.closing = false;
+
+    Ok(())
 }
 
 fn length_check(field: &String, min_accepted_length: usize, max_accepted_length: usize, is_mandatory: bool) -> Result<()> {
@@ -199,3 +390,54 @@ fn length_check(field: &String, min_accepted_length: usize, max_accepted_length:
     }  
     
 }
+
+// returns the counter of `user`'s index in `program_id`, a missing index counts as empty
+// both the friend and the membership index store the user followed by a u32 counter
+fn index_count(index: &AccountInfo, user: &Pubkey, seed: &[u8], program_id: &Pubkey) -> Option<u32> {
+    let (address, _) = Pubkey::find_program_address(&[&user.to_bytes()[..32], seed], program_id);
+    if index.key() != address {
+        return None
+    }
+    if index.owner != program_id {
+        return Some(0)
+    }
+
+    let data = index.try_borrow_data().ok()?;
+    let offset = DISCRIMINATOR_LENGTH + PUBKEY_USER_LENGTH;
+    let count = data.get(offset..offset + U32_LENGTH)?;
+    u32::try_from_slice(count).ok()
+}
+
+// the marker is paid out of the rent of the closed profile
+fn mark_closed<'info>(profile: &AccountInfo<'info>, marker: &AccountInfo<'info>, user: &Pubkey, system_program: &AccountInfo<'info>) -> Result<()> {
+    if marker.owner == &crate::ID {
+        return Ok(())
+    }
+
+    let user_bytes = user.to_bytes();
+    let (_, bump) = Pubkey::find_program_address(&[&user_bytes[..32], CLOSED_PROFILE_PDA_SEED], &crate::ID);
+    let signer_seeds: &[&[u8]] = &[&user_bytes[..32], CLOSED_PROFILE_PDA_SEED, &[bump]];
+    let missing_rent = Rent::get()?.minimum_balance(ClosedProfile::LEN).saturating_sub(marker.lamports());
+    **profile.try_borrow_mut_lamports()? -= missing_rent;
+    **marker.try_borrow_mut_lamports()? += missing_rent;
+
+    let cpi_accounts = system_program::Allocate {
+        account_to_allocate: marker.clone(),
+    };
+    system_program::allocate(CpiContext::new_with_signer(system_program.clone(), cpi_accounts, &[signer_seeds]), ClosedProfile::LEN as u64)?;
+    let cpi_accounts = system_program::Assign {
+        account_to_assign: marker.clone(),
+    };
+    system_program::assign(CpiContext::new_with_signer(system_program.clone(), cpi_accounts, &[signer_seeds]), &crate::ID)?;
+
+    let closed_profile = ClosedProfile { signer: *user };
+    let mut data = marker.try_borrow_mut_data()?;
+    closed_profile.try_serialize(&mut &mut data[..])
+}
+
+fn legacy_profile_check(account: &AccountInfo) -> bool {
+    match account.try_borrow_data() {
+        Ok(data) => data.len() < User::LEN && data.get(..DISCRIMINATOR_LENGTH) == Some(&User::discriminator()[..]),
+        Err(_) => false
+    }
+}
@@ -0,0 +1,244 @@
+// Generates the accounts preloaded by `anchor test` (see `[[test.validator.account]]`
+// in Anchor.toml). They stand for state written by earlier program versions, which
+// the current programs can no longer create themselves.
+//
+// Usage: node tests/fixtures/generate.js
+const crypto = require('crypto')
+const fs = require('fs')
+const path = require('path')
+
+const FRIENDS_PROGRAM_ID = '8MnP9XdAKdCvyK6KjRgu9AAGRAb7GmNxSDakqt5z8jTM'
+const GROUPCHATS_PROGRAM_ID = 'EGSLBxSQyf8aYgV8gavHy2qhdwdrCRunap6Tv3c7YJWP'
+const USERS_PROGRAM_ID = '3xjXCBLnaC1Vh7VuqA1SK5G1gpktENVBXMbqJtXMTGZT'
+const LEGACY_USER_LEN = 512
+const LEGACY_GROUP_LEN = 142
+const LEGACY_INVITATION_LEN = 337
+
+const BASE58_ALPHABET =
+  '123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz'
+
+const encodeBase58 = (bytes) => {
+  let value = BigInt('0x' + (Buffer.from(bytes).toString('hex') || '0'))
+  let encoded = ''
+  while (value > 0n) {
+    encoded = BASE58_ALPHABET[Number(value % 58n)] + encoded
+    value /= 58n
+  }
+  for (const byte of bytes) {
+    if (byte !== 0) break
+    encoded = '1' + encoded
+  }
+  return encoded
+}
+
+const decodeBase58 = (text) => {
+  let value = 0n
+  for (const char of text) {
+    value = value * 58n + BigInt(BASE58_ALPHABET.indexOf(char))
+  }
+  const bytes = Buffer.from(value.toString(16).padStart(64, '0'), 'hex')
+  return bytes
+}
+
+// same keys as `anchor.web3.Keypair.fromSeed(Buffer.alloc(32, fill))` in the tests
+const publicKeyFromFill = (fill) => {
+  const pkcs8Prefix = Buffer.from('302e020100300506032b657004220420', 'hex')
+  const privateKey = crypto.createPrivateKey({
+    key: Buffer.concat([pkcs8Prefix, Buffer.alloc(32, fill)]),
+    format: 'der',
+    type: 'pkcs8',
+  })
+  const jwk = crypto.createPublicKey(privateKey).export({ format: 'jwk' })
+  return Buffer.from(jwk.x, 'base64url')
+}
+
+const P = 2n ** 255n - 19n
+
+const modPow = (base, exponent) => {
+  let result = 1n
+  base %= P
+  while (exponent > 0n) {
+    if (exponent & 1n) result = (result * base) % P
+    base = (base * base) % P
+    exponent >>= 1n
+  }
+  return result
+}
+
+// a point decompresses when (y^2 - 1) / (d y^2 + 1) is a square
+const isOnCurve = (bytes) => {
+  const y =
+    BigInt('0x' + Buffer.from(bytes).reverse().toString('hex')) &
+    ((1n << 255n) - 1n)
+  const d = (-121665n * modPow(121666n, P - 2n)) % P
+  const y2 = (y * y) % P
+  const u = (y2 - 1n + P) % P
+  const v = (((d * y2) % P) + 1n + P) % P
+  const x2 = (u * modPow(v, P - 2n)) % P
+  return x2 === 0n || modPow(x2, (P - 1n) / 2n) === 1n
+}
+
+const findProgramAddress = (seeds, programId) => {
+  for (let bump = 255; bump >= 0; bump--) {
+    const address = crypto
+      .createHash('sha256')
+      .update(Buffer.concat([...seeds, Buffer.from([bump])]))
+      .update(decodeBase58(programId))
+      .update('ProgramDerivedAddress')
+      .digest()
+    if (!isOnCurve(address)) return address
+  }
+  throw new Error('no program address found')
+}
+
+const discriminator = (name) =>
+  crypto.createHash('sha256').update(`account:${name}`).digest().slice(0, 8)
+
+const u8 = (value) => Buffer.from([value])
+
+const string = (value) => {
+  const length = Buffer.alloc(4)
+  length.writeUInt32LE(Buffer.byteLength(value))
+  return Buffer.concat([length, Buffer.from(value)])
+}
+
+const rentExempt = (length) => (length + 128) * 3480 * 2
+
+const writeFixture = (name, address, programId, length, fields) => {
+  const data = Buffer.alloc(length)
+  Buffer.concat(fields).copy(data)
+  const fixture = {
+    pubkey: encodeBase58(address),
+    account: {
+      lamports: rentExempt(length),
+      data: [data.toString('base64'), 'base64'],
+      owner: programId,
+      executable: false,
+      rentEpoch: 0,
+    },
+  }
+  fs.writeFileSync(
+    path.join(__dirname, `${name}.json`),
+    JSON.stringify(fixture, null, 2) + '\n',
+  )
+  console.log(`${name}: ${fixture.pubkey}`)
+}
+
+// friendship accepted before the friend index existed, so neither side lists it
+const legacyFriendship = (name, fill1, fill2) => {
+  const [user1, user2] = [
+    publicKeyFromFill(fill1),
+    publicKeyFromFill(fill2),
+  ].sort((a, b) => Buffer.compare(b, a))
+  const key = 'legacyfriendshipkey'.repeat(4)
+  writeFixture(
+    name,
+    findProgramAddress([user1, user2], FRIENDS_PROGRAM_ID),
+    FRIENDS_PROGRAM_ID,
+    369,
+    [
+      discriminator('FriendRequest'),
+      user1,
+      u8(2),
+      user2,
+      user1,
+      string(key),
+      string(key),
+    ],
+  )
+}
+
+// profile written before `closing` existed, every field at full length
+const legacyProfile = () => {
+  const user = publicKeyFromFill(9)
+  writeFixture(
+    'legacy_profile',
+    findProgramAddress([user, Buffer.from('user')], USERS_PROGRAM_ID),
+    USERS_PROGRAM_ID,
+    LEGACY_USER_LEN,
+    [
+      discriminator('User'),
+      string('n'.repeat(32)),
+      user,
+      user,
+      string('p'.repeat(64)),
+      string('s'.repeat(128)),
+      string('b'.repeat(64)),
+      string('1'.repeat(64)),
+      string('2'.repeat(64)),
+    ],
+  )
+}
+
+const legacyInvitation = (name, sender, group, recipient, groupId) =>
+  writeFixture(
+    name,
+    findProgramAddress(
+      [recipient, group, Buffer.from('invite')],
+      GROUPCHATS_PROGRAM_ID,
+    ),
+    GROUPCHATS_PROGRAM_ID,
+    LEGACY_INVITATION_LEN,
+    [
+      discriminator('Invitation'),
+      sender,
+      group,
+      recipient,
+      string(groupId),
+      string('legacyencryptionkey'.repeat(4)),
+      u8(1),
+    ],
+  )
+
+const legacyGroupKey = (groupId, admin) => {
+  const hash = crypto
+    .createHash('sha256')
+    .update(groupId)
+    .update(admin)
+    .digest()
+  return findProgramAddress(
+    [hash, Buffer.from('groupchat')],
+    GROUPCHATS_PROGRAM_ID,
+  )
+}
+
+// group with three members, written before groups had a roster or membership index
+const legacyGroup = () => {
+  const admin = publicKeyFromFill(3)
+  const member = publicKeyFromFill(4)
+  const staleMember = publicKeyFromFill(6)
+  const groupId = 'legacygroup'
+  const group = legacyGroupKey(groupId, admin)
+  writeFixture('legacy_group', group, GROUPCHATS_PROGRAM_ID, LEGACY_GROUP_LEN, [
+    discriminator('Group'),
+    admin,
+    admin,
+    u8(1),
+    u8(3),
+    string('Legacy group'),
+  ])
+  legacyInvitation('legacy_admin_invitation', admin, group, admin, groupId)
+  legacyInvitation('legacy_member_invitation', admin, group, member, groupId)
+  // never migrated, left behind once the group is dissolved
+  legacyInvitation('legacy_stale_invitation', admin, group, staleMember, groupId)
+}
+
+// invitation left behind by a legacy group that no longer exists
+const legacyOrphanInvitation = () => {
+  const admin = publicKeyFromFill(3)
+  const groupId = 'legacyclosedgroup'
+  legacyInvitation(
+    'legacy_orphan_invitation',
+    admin,
+    legacyGroupKey(groupId, admin),
+    publicKeyFromFill(5),
+    groupId,
+  )
+}
+
+legacyFriendship('legacy_friend_request', 1, 2)
+// its first side closes the profile before anyone indexed it
+legacyFriendship('legacy_closed_friend_request', 7, 8)
+legacyProfile()
+legacyGroup()
+legacyOrphanInvitation()
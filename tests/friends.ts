@@ -1,6 +1,7 @@
 import * as anchor from '@project-serum/anchor'
 import { Program } from '@project-serum/anchor'
 import { Friends } from '../target/types/friends'
+import { Users } from '../target/types/users'
 import assert from 'assert'
 import { associatedAddress } from '@project-serum/anchor/dist/cjs/utils/token'
 import microbs58 from 'micro-base58'
@@ -13,6 +14,7 @@ describe('friends', () => {
   anchor.setProvider(provider)
 
   const program = anchor.workspace.Friends as Program<Friends>
+  const usersProgram = anchor.workspace.Users as Program<Users>
 
   let user1 = anchor.web3.Keypair.generate()
   let user2 = anchor.web3.Keypair.generate()
@@ -73,6 +75,132 @@ describe('friends', () => {
 
   const k = 'dhfskjdfhsdjkfhsdjkfhdsjkhdjkfds'.repeat(4)
 
+  const friendIndexSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('friendindex'),
+  )
+  const friendPageSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('friendpage'),
+  )
+
+  const friendIndex = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), friendIndexSeed],
+      program.programId,
+    )[0]
+
+  const friendPage = (user: anchor.web3.PublicKey, page: number) => {
+    const pageBytes = Buffer.alloc(4)
+    pageBytes.writeUInt32LE(page)
+    return anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), pageBytes, friendPageSeed],
+      program.programId,
+    )[0]
+  }
+
+  const indexedSeed = Buffer.from(anchor.utils.bytes.utf8.encode('indexed'))
+
+  const indexedFriendship = (request: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [request.toBuffer(), indexedSeed],
+      program.programId,
+    )[0]
+
+  // friendship preloaded from tests/fixtures, accepted before the friend index existed
+  let legacyFriend1 = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 1))
+  let legacyFriend2 = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 2))
+  if (
+    Buffer.compare(
+      legacyFriend1.publicKey.toBuffer(),
+      legacyFriend2.publicKey.toBuffer(),
+    ) < 0
+  ) {
+    ;[legacyFriend1, legacyFriend2] = [legacyFriend2, legacyFriend1]
+  }
+  const legacyRequest = anchor.utils.publicKey.findProgramAddressSync(
+    [legacyFriend1.publicKey.toBuffer(), legacyFriend2.publicKey.toBuffer()],
+    program.programId,
+  )[0]
+
+  const indexLegacyFriendship = async () => {
+    await program.rpc.indexFriendship({
+      accounts: {
+        request: legacyRequest,
+        indexed: indexedFriendship(legacyRequest),
+        fromIndex: friendIndex(legacyFriend1.publicKey),
+        fromPage: friendPage(legacyFriend1.publicKey, 0),
+        toIndex: friendIndex(legacyFriend2.publicKey),
+        toPage: friendPage(legacyFriend2.publicKey, 0),
+        payer: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+    })
+  }
+
+  it('Anyone indexes a friendship accepted before the friend index', async () => {
+    await indexLegacyFriendship()
+
+    const index1 = await program.account.friendIndex.fetch(
+      friendIndex(legacyFriend1.publicKey),
+    )
+    const page2 = await program.account.friendPage.fetch(
+      friendPage(legacyFriend2.publicKey, 0),
+    )
+    const indexed = await program.account.indexedFriendship.fetch(
+      indexedFriendship(legacyRequest),
+    )
+    assert.ok(index1.friends == 1)
+    assert.ok(page2.friends[0].equals(legacyFriend1.publicKey))
+    assert.ok(indexed.payer.equals(provider.wallet.publicKey))
+  })
+
+  it('Friendship cannot be indexed twice', async () => {
+    try {
+      await indexLegacyFriendship()
+      assert.ok(false)
+    } catch (err) {
+      assert.ok(err.logs.some((log) => log.includes('already in use')))
+    }
+  })
+
+  it('Backfilled friendship is removed and closed', async () => {
+    await program.rpc.removeFriend({
+      accounts: {
+        request: legacyRequest,
+        fromIndex: friendIndex(legacyFriend1.publicKey),
+        fromPage: friendPage(legacyFriend1.publicKey, 0),
+        fromLastPage: friendPage(legacyFriend1.publicKey, 0),
+        toIndex: friendIndex(legacyFriend2.publicKey),
+        toPage: friendPage(legacyFriend2.publicKey, 0),
+        toLastPage: friendPage(legacyFriend2.publicKey, 0),
+        user: legacyFriend2.publicKey,
+        indexed: indexedFriendship(legacyRequest),
+        indexedPayer: provider.wallet.publicKey,
+      },
+      signers: [legacyFriend2],
+    })
+
+    const index2 = await program.account.friendIndex.fetch(
+      friendIndex(legacyFriend2.publicKey),
+    )
+    assert.ok(index2.friends == 0)
+    assert.equal(
+      await provider.connection.getAccountInfo(
+        indexedFriendship(legacyRequest),
+      ),
+      null,
+    )
+
+    // the request counts below only cover the requests made by these tests
+    await program.rpc.closeRequest({
+      accounts: {
+        request: legacyRequest,
+        user: legacyFriend2.publicKey,
+        payer: legacyFriend1.publicKey,
+      },
+      signers: [legacyFriend2],
+    })
+  })
+
   it('User 1 cannot create a new request for user 2 (payer user 1) with different order of accounts', async () => {
     // Airdropping tokens to a payer.
     await provider.connection.confirmTransaction(
@@ -773,7 +901,14 @@ describe('friends', () => {
       await program.rpc.acceptRequest(k, {
         accounts: {
           request: request[0],
+          indexed: indexedFriendship(request[0]),
+          fromIndex: friendIndex(user1.publicKey),
+          fromPage: friendPage(user1.publicKey, 0),
+          toIndex: friendIndex(user2.publicKey),
+          toPage: friendPage(user2.publicKey, 0),
           user: user1.publicKey,
+          payer: user1.publicKey,
+          systemProgram: SystemProgram.programId,
         },
         signers: [user1],
       })
@@ -844,7 +979,14 @@ describe('friends', () => {
       await program.rpc.acceptRequest(k, {
         accounts: {
           request: request[0],
+          indexed: indexedFriendship(request[0]),
+          fromIndex: friendIndex(user1.publicKey),
+          fromPage: friendPage(user1.publicKey, 0),
+          toIndex: friendIndex(user2.publicKey),
+          toPage: friendPage(user2.publicKey, 0),
           user: otherUser.publicKey,
+          payer: otherUser.publicKey,
+          systemProgram: SystemProgram.programId,
         },
         signers: [otherUser],
       })
@@ -914,7 +1056,14 @@ describe('friends', () => {
     await program.rpc.acceptRequest(k, {
       accounts: {
         request: request[0],
+        indexed: indexedFriendship(request[0]),
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
         user: user2.publicKey,
+        payer: user2.publicKey,
+        systemProgram: SystemProgram.programId,
       },
       signers: [user2],
     })
@@ -977,12 +1126,45 @@ describe('friends', () => {
     assert.ok(requestAccountsRequestRemoved.length == 0)
   })
 
+  it('Friend index of both users lists the other after accepting', async () => {
+    const index1 = await program.account.friendIndex.fetch(
+      friendIndex(user1.publicKey),
+    )
+    const page1 = await program.account.friendPage.fetch(
+      friendPage(user1.publicKey, 0),
+    )
+    const index2 = await program.account.friendIndex.fetch(
+      friendIndex(user2.publicKey),
+    )
+    const page2 = await program.account.friendPage.fetch(
+      friendPage(user2.publicKey, 0),
+    )
+
+    assert.ok(index1.user.equals(user1.publicKey))
+    assert.ok(index1.friends == 1)
+    assert.ok(page1.page == 0)
+    assert.ok(page1.friends.length == 1)
+    assert.ok(page1.friends[0].equals(user2.publicKey))
+    assert.ok(page1.payer.equals(user2.publicKey))
+    assert.ok(index2.user.equals(user2.publicKey))
+    assert.ok(index2.friends == 1)
+    assert.ok(page2.friends.length == 1)
+    assert.ok(page2.friends[0].equals(user1.publicKey))
+  })
+
   it('User 2 cannot accept a request already accepted from user 1', async () => {
     try {
       await program.rpc.acceptRequest(k, {
         accounts: {
           request: request[0],
+          indexed: indexedFriendship(request[0]),
+          fromIndex: friendIndex(user1.publicKey),
+          fromPage: friendPage(user1.publicKey, 0),
+          toIndex: friendIndex(user2.publicKey),
+          toPage: friendPage(user2.publicKey, 0),
           user: user2.publicKey,
+          payer: user2.publicKey,
+          systemProgram: SystemProgram.programId,
         },
         signers: [user2],
       })
@@ -1355,7 +1537,15 @@ describe('friends', () => {
       await program.rpc.removeFriend({
         accounts: {
           request: request[0],
+          fromIndex: friendIndex(user1.publicKey),
+          fromPage: friendPage(user1.publicKey, 0),
+          fromLastPage: friendPage(user1.publicKey, 0),
+          toIndex: friendIndex(user2.publicKey),
+          toPage: friendPage(user2.publicKey, 0),
+          toLastPage: friendPage(user2.publicKey, 0),
           user: otherUser.publicKey,
+          indexed: indexedFriendship(request[0]),
+          indexedPayer: user2.publicKey,
         },
         signers: [otherUser],
       })
@@ -1426,7 +1616,15 @@ describe('friends', () => {
     await program.rpc.removeFriend({
       accounts: {
         request: request[0],
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        fromLastPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
+        toLastPage: friendPage(user2.publicKey, 0),
         user: user1.publicKey,
+        indexed: indexedFriendship(request[0]),
+        indexedPayer: user2.publicKey,
       },
       signers: [user1],
     })
@@ -1489,12 +1687,75 @@ describe('friends', () => {
     assert.ok(requestAccountsRequestRemoved.length == 0)
   })
 
+  it('Friend index of both users is emptied after removing friend', async () => {
+    const index1 = await program.account.friendIndex.fetch(
+      friendIndex(user1.publicKey),
+    )
+    const page1 = await program.account.friendPage.fetch(
+      friendPage(user1.publicKey, 0),
+    )
+    const index2 = await program.account.friendIndex.fetch(
+      friendIndex(user2.publicKey),
+    )
+    const page2 = await program.account.friendPage.fetch(
+      friendPage(user2.publicKey, 0),
+    )
+
+    assert.ok(index1.friends == 0)
+    assert.ok(page1.friends.length == 0)
+    assert.ok(index2.friends == 0)
+    assert.ok(page2.friends.length == 0)
+  })
+
+  it('Emptied friend index is refunded to the payer that created it', async () => {
+    try {
+      await program.rpc.closeFriendIndex({
+        accounts: {
+          index: friendIndex(user1.publicKey),
+          payer: user1.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account was not created by provided user'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    await program.rpc.closeFriendPage({
+      accounts: {
+        page: friendPage(user1.publicKey, 0),
+        payer: user2.publicKey,
+      },
+    })
+    await program.rpc.closeFriendIndex({
+      accounts: {
+        index: friendIndex(user1.publicKey),
+        payer: user2.publicKey,
+      },
+    })
+
+    for (const account of [
+      friendIndex(user1.publicKey),
+      friendPage(user1.publicKey, 0),
+    ]) {
+      assert.equal(await provider.connection.getAccountInfo(account), null)
+    }
+  })
+
   it('User 1 cannot remove friend from removed request because they are not friend anymore', async () => {
     try {
       await program.rpc.removeFriend({
         accounts: {
           request: request[0],
+          fromIndex: friendIndex(user1.publicKey),
+          fromPage: friendPage(user1.publicKey, 0),
+          fromLastPage: friendPage(user1.publicKey, 0),
+          toIndex: friendIndex(user2.publicKey),
+          toPage: friendPage(user2.publicKey, 0),
+          toLastPage: friendPage(user2.publicKey, 0),
           user: user1.publicKey,
+          indexed: indexedFriendship(request[0]),
+          indexedPayer: user2.publicKey,
         },
         signers: [user1],
       })
@@ -1639,7 +1900,14 @@ describe('friends', () => {
     await program.rpc.acceptRequest(k, {
       accounts: {
         request: request[0],
+        indexed: indexedFriendship(request[0]),
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
         user: user2.publicKey,
+        payer: user2.publicKey,
+        systemProgram: SystemProgram.programId,
       },
       signers: [user2],
     })
@@ -1850,7 +2118,15 @@ describe('friends', () => {
       await program.rpc.removeFriend({
         accounts: {
           request: request[0],
+          fromIndex: friendIndex(user1.publicKey),
+          fromPage: friendPage(user1.publicKey, 0),
+          fromLastPage: friendPage(user1.publicKey, 0),
+          toIndex: friendIndex(user2.publicKey),
+          toPage: friendPage(user2.publicKey, 0),
+          toLastPage: friendPage(user2.publicKey, 0),
           user: user2.publicKey,
+          indexed: indexedFriendship(request[0]),
+          indexedPayer: user2.publicKey,
         },
         signers: [user2],
       })
@@ -2248,7 +2524,14 @@ describe('friends', () => {
     await program.rpc.acceptRequest(k, {
       accounts: {
         request: request[0],
+        indexed: indexedFriendship(request[0]),
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
         user: user2.publicKey,
+        payer: user2.publicKey,
+        systemProgram: SystemProgram.programId,
       },
       signers: [user2],
     })
@@ -2256,7 +2539,15 @@ describe('friends', () => {
     await program.rpc.removeFriend({
       accounts: {
         request: request[0],
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        fromLastPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
+        toLastPage: friendPage(user2.publicKey, 0),
         user: user1.publicKey,
+        indexed: indexedFriendship(request[0]),
+        indexedPayer: user2.publicKey,
       },
       signers: [user1],
     })
@@ -2346,7 +2637,14 @@ describe('friends', () => {
     await program.rpc.acceptRequest(k, {
       accounts: {
         request: request[0],
+        indexed: indexedFriendship(request[0]),
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
         user: user2.publicKey,
+        payer: user2.publicKey,
+        systemProgram: SystemProgram.programId,
       },
       signers: [user2],
     })
@@ -2426,7 +2724,15 @@ describe('friends', () => {
     await program.rpc.removeFriend({
       accounts: {
         request: request[0],
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        fromLastPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
+        toLastPage: friendPage(user2.publicKey, 0),
         user: user1.publicKey,
+        indexed: indexedFriendship(request[0]),
+        indexedPayer: user2.publicKey,
       },
       signers: [user1],
     })
@@ -2586,7 +2892,14 @@ describe('friends', () => {
     await program.rpc.acceptRequest(k, {
       accounts: {
         request: request[0],
+        indexed: indexedFriendship(request[0]),
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
         user: user2.publicKey,
+        payer: user2.publicKey,
+        systemProgram: SystemProgram.programId,
       },
       signers: [user2],
     })
@@ -2594,7 +2907,15 @@ describe('friends', () => {
     await program.rpc.removeFriend({
       accounts: {
         request: request[0],
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        fromLastPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
+        toLastPage: friendPage(user2.publicKey, 0),
         user: user1.publicKey,
+        indexed: indexedFriendship(request[0]),
+        indexedPayer: user2.publicKey,
       },
       signers: [user1],
     })
@@ -2994,4 +3315,351 @@ describe('friends', () => {
     assert.ok(requestAccountsFriendRemoved.length == 0)
     assert.ok(requestAccountsRequestRemoved.length == 0)
   })
+
+  it('Only the current payer can transfer the request payer', async () => {
+    try {
+      await program.rpc.transferPayer({
+        accounts: {
+          request: newRequest[0],
+          user: user4.publicKey,
+          payer: otherUser.publicKey,
+          newPayer: otherUser.publicKey,
+        },
+        signers: [user4, otherUser],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account was not created by provided user'
+      assert.equal(errMsg, err.msg)
+    }
+  })
+
+  it('Extern user cannot co-sign a payer transfer', async () => {
+    try {
+      await program.rpc.transferPayer({
+        accounts: {
+          request: newRequest[0],
+          user: otherUser.publicKey,
+          payer: otherPayer.publicKey,
+          newPayer: otherUser.publicKey,
+        },
+        signers: [otherUser, otherPayer],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = "User can't perform this action"
+      assert.equal(errMsg, err.msg)
+    }
+  })
+
+  it('Payer transfers the request refund to another wallet', async () => {
+    await program.rpc.transferPayer({
+      accounts: {
+        request: newRequest[0],
+        user: user4.publicKey,
+        payer: otherPayer.publicKey,
+        newPayer: otherUser.publicKey,
+      },
+      signers: [user4, otherPayer],
+    })
+
+    const requestAccount = await program.account.friendRequest.fetch(
+      newRequest[0],
+    )
+    assert.ok(requestAccount.payer.equals(otherUser.publicKey))
+  })
+
+  const userSeed = Buffer.from(anchor.utils.bytes.utf8.encode('user'))
+  const userProfile = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), userSeed],
+      usersProgram.programId,
+    )[0]
+  const closedProfile = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), Buffer.from('closedprofile')],
+      usersProgram.programId,
+    )[0]
+
+  it('Friends of an active profile cannot be removed by others', async () => {
+    await program.rpc.acceptRequest(k, {
+      accounts: {
+        request: request[0],
+        indexed: indexedFriendship(request[0]),
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
+        user: user2.publicKey,
+        payer: user2.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [user2],
+    })
+    await usersProgram.rpc.create('User 1', '', '', {
+      accounts: {
+        user: userProfile(user1.publicKey),
+        signer: user1.publicKey,
+        payer: user1.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [user1],
+    })
+
+    try {
+      await program.rpc.removeDeactivatedFriend({
+        accounts: {
+          request: request[0],
+          user: userProfile(user1.publicKey),
+          closedProfile: closedProfile(user1.publicKey),
+          fromIndex: friendIndex(user1.publicKey),
+          fromPage: friendPage(user1.publicKey, 0),
+          fromLastPage: friendPage(user1.publicKey, 0),
+          toIndex: friendIndex(user2.publicKey),
+          toPage: friendPage(user2.publicKey, 0),
+          toLastPage: friendPage(user2.publicKey, 0),
+          indexed: indexedFriendship(request[0]),
+          indexedPayer: user2.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User profile is not deactivated'
+      assert.equal(errMsg, err.msg)
+    }
+  })
+
+  const removeDeactivatedFriend = async (
+    user: anchor.web3.PublicKey,
+    userClosedProfile: anchor.web3.PublicKey,
+  ) => {
+    await program.rpc.removeDeactivatedFriend({
+      accounts: {
+        request: request[0],
+        user,
+        closedProfile: userClosedProfile,
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        fromLastPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
+        toLastPage: friendPage(user2.publicKey, 0),
+        indexed: indexedFriendship(request[0]),
+        indexedPayer: user2.publicKey,
+      },
+    })
+  }
+
+  it('Only the profile accounts of a friend are accepted', async () => {
+    const errMsg = "Addresses in request don't match user address"
+    try {
+      await removeDeactivatedFriend(
+        user1.publicKey,
+        closedProfile(user1.publicKey),
+      )
+      assert.ok(false)
+    } catch (err) {
+      assert.equal(errMsg, err.msg)
+    }
+    try {
+      await removeDeactivatedFriend(
+        userProfile(user1.publicKey),
+        closedProfile(user2.publicKey),
+      )
+      assert.ok(false)
+    } catch (err) {
+      assert.equal(errMsg, err.msg)
+    }
+    try {
+      await removeDeactivatedFriend(
+        userProfile(otherUser.publicKey),
+        closedProfile(otherUser.publicKey),
+      )
+      assert.ok(false)
+    } catch (err) {
+      assert.equal(errMsg, err.msg)
+    }
+  })
+
+  it('Friends of a deactivated profile are removed permissionlessly', async () => {
+    await usersProgram.rpc.deactivate({
+      accounts: {
+        user: userProfile(user1.publicKey),
+        signer: user1.publicKey,
+      },
+      signers: [user1],
+    })
+
+    await program.rpc.removeDeactivatedFriend({
+      accounts: {
+        request: request[0],
+        user: userProfile(user1.publicKey),
+        closedProfile: closedProfile(user1.publicKey),
+        fromIndex: friendIndex(user1.publicKey),
+        fromPage: friendPage(user1.publicKey, 0),
+        fromLastPage: friendPage(user1.publicKey, 0),
+        toIndex: friendIndex(user2.publicKey),
+        toPage: friendPage(user2.publicKey, 0),
+        toLastPage: friendPage(user2.publicKey, 0),
+        indexed: indexedFriendship(request[0]),
+        indexedPayer: user2.publicKey,
+      },
+    })
+
+    let requestAccount = await program.account.friendRequest.fetch(request[0])
+    let toIndexAccount = await program.account.friendIndex.fetch(
+      friendIndex(user2.publicKey),
+    )
+    assert.ok(Object.keys(requestAccount.status)[0] == 'removedFriend')
+    assert.ok(toIndexAccount.friends == 0)
+  })
+
+  it('Requests of a deactivated profile are closed permissionlessly', async () => {
+    try {
+      await program.rpc.closeDeactivatedRequest({
+        accounts: {
+          request: newRequest[0],
+          user: userProfile(user1.publicKey),
+          closedProfile: closedProfile(user1.publicKey),
+          payer: otherUser.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = "Addresses in request don't match user address"
+      assert.equal(errMsg, err.msg)
+    }
+
+    await program.rpc.closeDeactivatedRequest({
+      accounts: {
+        request: request[0],
+        user: userProfile(user1.publicKey),
+        closedProfile: closedProfile(user1.publicKey),
+        payer: user1.publicKey,
+      },
+    })
+
+    let requestAccount = await program.account.friendRequest.fetchNullable(
+      request[0],
+    )
+    assert.ok(requestAccount == null)
+
+    await usersProgram.rpc.close({
+      accounts: {
+        user: userProfile(user1.publicKey),
+        signer: user1.publicKey,
+        payer: user1.publicKey,
+        friendIndex: friendIndex(user1.publicKey),
+        membershipIndex: anchor.utils.publicKey.findProgramAddressSync(
+          [user1.publicKey.toBuffer(), Buffer.from('membership')],
+          anchor.workspace.Groupchats.programId,
+        )[0],
+        closedProfile: closedProfile(user1.publicKey),
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [user1],
+    })
+  })
+
+  // preloaded from tests/fixtures, never indexed before one side closed
+  const closedFriend = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 7))
+  const remainingFriend = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 8))
+  const [closedFrom, closedTo] =
+    Buffer.compare(
+      closedFriend.publicKey.toBuffer(),
+      remainingFriend.publicKey.toBuffer(),
+    ) > 0
+      ? [closedFriend.publicKey, remainingFriend.publicKey]
+      : [remainingFriend.publicKey, closedFriend.publicKey]
+  const closedRequest = anchor.utils.publicKey.findProgramAddressSync(
+    [closedFrom.toBuffer(), closedTo.toBuffer()],
+    program.programId,
+  )[0]
+
+  const removeClosedFriendship = async (user: anchor.web3.PublicKey) => {
+    await program.rpc.removeDeactivatedFriend({
+      accounts: {
+        request: closedRequest,
+        user: userProfile(user),
+        closedProfile: closedProfile(user),
+        fromIndex: friendIndex(closedFrom),
+        fromPage: friendPage(closedFrom, 0),
+        fromLastPage: friendPage(closedFrom, 0),
+        toIndex: friendIndex(closedTo),
+        toPage: friendPage(closedTo, 0),
+        toLastPage: friendPage(closedTo, 0),
+        indexed: indexedFriendship(closedRequest),
+        indexedPayer: provider.wallet.publicKey,
+      },
+    })
+  }
+
+  it('Unindexed friendship of a closed profile is still torn down', async () => {
+    const profile = userProfile(closedFriend.publicKey)
+    await usersProgram.rpc.create('Closed', '', '', {
+      accounts: {
+        user: profile,
+        signer: closedFriend.publicKey,
+        payer: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [closedFriend],
+    })
+    await usersProgram.rpc.deactivate({
+      accounts: {
+        user: profile,
+        signer: closedFriend.publicKey,
+      },
+      signers: [closedFriend],
+    })
+    // the friend index never counted the legacy friendship
+    await usersProgram.rpc.close({
+      accounts: {
+        user: profile,
+        signer: closedFriend.publicKey,
+        payer: provider.wallet.publicKey,
+        friendIndex: friendIndex(closedFriend.publicKey),
+        membershipIndex: anchor.utils.publicKey.findProgramAddressSync(
+          [closedFriend.publicKey.toBuffer(), Buffer.from('membership')],
+          anchor.workspace.Groupchats.programId,
+        )[0],
+        closedProfile: closedProfile(closedFriend.publicKey),
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [closedFriend],
+    })
+
+    await program.rpc.indexFriendship({
+      accounts: {
+        request: closedRequest,
+        indexed: indexedFriendship(closedRequest),
+        fromIndex: friendIndex(closedFrom),
+        fromPage: friendPage(closedFrom, 0),
+        toIndex: friendIndex(closedTo),
+        toPage: friendPage(closedTo, 0),
+        payer: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+    })
+
+    // a side that never had a profile is not taken for a closed one
+    try {
+      await removeClosedFriendship(remainingFriend.publicKey)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User profile is not deactivated'
+      assert.equal(errMsg, err.msg)
+    }
+
+    await removeClosedFriendship(closedFriend.publicKey)
+
+    const requestAccount = await program.account.friendRequest.fetch(
+      closedRequest,
+    )
+    const remainingIndex = await program.account.friendIndex.fetch(
+      friendIndex(remainingFriend.publicKey),
+    )
+    assert.ok(Object.keys(requestAccount.status)[0] == 'removedFriend')
+    assert.ok(remainingIndex.friends == 0)
+  })
 })
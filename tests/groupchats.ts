@@ -1,7 +1,10 @@
 import * as anchor from '@project-serum/anchor'
 import { Program } from '@project-serum/anchor'
 import { Groupchats } from '../target/types/groupchats'
+import { Users } from '../target/types/users'
+import { Friends } from '../target/types/friends'
 import assert from 'assert'
+import { createHash } from 'crypto'
 const { SystemProgram } = anchor.web3
 
 describe('groupchats', () => {
@@ -12,27 +15,103 @@ describe('groupchats', () => {
 
   // Program for the tests.
   const program = anchor.workspace.Groupchats as Program<Groupchats>
+  const usersProgram = anchor.workspace.Users as Program<Users>
+  const friendsProgram = anchor.workspace.Friends as Program<Friends>
 
   const groupSeed = Buffer.from(anchor.utils.bytes.utf8.encode('groupchat'))
   const inviteSeed = Buffer.from(anchor.utils.bytes.utf8.encode('invite'))
+  const membershipIndexSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('membership'),
+  )
+  const membershipPageSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('membershippage'),
+  )
+
+  const rosterSeed = Buffer.from(anchor.utils.bytes.utf8.encode('roster'))
+
+  const rosterPage = (group: anchor.web3.PublicKey, page: number) => {
+    const pageBytes = Buffer.alloc(4)
+    pageBytes.writeUInt32LE(page)
+    return anchor.utils.publicKey.findProgramAddressSync(
+      [group.toBuffer(), pageBytes, rosterSeed],
+      program.programId,
+    )[0]
+  }
+
+  const membershipIndex = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), membershipIndexSeed],
+      program.programId,
+    )[0]
+
+  const membershipPage = (user: anchor.web3.PublicKey, page: number) => {
+    const pageBytes = Buffer.alloc(4)
+    pageBytes.writeUInt32LE(page)
+    return anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), pageBytes, membershipPageSeed],
+      program.programId,
+    )[0]
+  }
 
   let groupId =
     'dhfskjdfhsdjkfhsdjkfhdsjkhdjkfdhfskjdfhsdjkfhsdjkfhdsjkhdjkfdfrt'
-  const groupHash = Buffer.from(anchor.utils.sha256.hash(groupId), 'hex')
+  const user1 = anchor.web3.Keypair.generate()
+  const groupHash = createHash('sha256')
+    .update(groupId)
+    .update(user1.publicKey.toBuffer())
+    .digest()
 
   let name = 'dhfskjdfhsdjkfh'
 
   let encryptionKey =
     'dhfskjdfhsdjkfhsdjkfhdsjkhdjkfdhfskjdfhsdjkfhsdjkfhdsjkhdjkfdfrt'
 
-  let dbType = 1
+  let dbType = { textile: {} }
+
+  let visibility = { public: {} }
+
+  const category = 0
+
+  let description = 'dhfskjdfhsdjkfh dhfskjdfhsdjkfh'
+
+  let tags = ['sdkjf', 'dfkjg']
+
+  const directorySeed = Buffer.from(anchor.utils.bytes.utf8.encode('directory'))
+  const directoryPageSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('directorypage'),
+  )
+
+  const directory = (category: number) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [Buffer.from([category]), directorySeed],
+      program.programId,
+    )[0]
+
+  const directoryPage = (category: number, page: number) => {
+    const pageBytes = Buffer.alloc(4)
+    pageBytes.writeUInt32LE(page)
+    return anchor.utils.publicKey.findProgramAddressSync(
+      [Buffer.from([category]), pageBytes, directoryPageSeed],
+      program.programId,
+    )[0]
+  }
+
+  // only public groups pass their directory accounts, as remaining accounts
+  const directoryAccounts = (category: number) => [
+    { pubkey: directory(category), isWritable: true, isSigner: false },
+    { pubkey: directoryPage(category, 0), isWritable: true, isSigner: false },
+  ]
+
+  const listedGroupAccounts = (category: number) => [
+    ...directoryAccounts(category),
+    { pubkey: directoryPage(category, 0), isWritable: true, isSigner: false },
+  ]
 
   // Accounts for the tests.
   const group = anchor.utils.publicKey.findProgramAddressSync(
     [groupHash, groupSeed],
     program.programId,
   )
-  const user1 = anchor.web3.Keypair.generate()
   const inv1 = anchor.utils.publicKey.findProgramAddressSync(
     [user1.publicKey.toBytes(), group[0].toBytes(), inviteSeed],
     program.programId,
@@ -53,6 +132,89 @@ describe('groupchats', () => {
     program.programId,
   )
 
+  const airdrop = async (user: anchor.web3.Keypair) => {
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(user.publicKey, 10000000000),
+      'confirmed',
+    )
+  }
+
+  const invitation = (
+    user: anchor.web3.PublicKey,
+    group: anchor.web3.PublicKey,
+  ) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBytes(), group.toBytes(), inviteSeed],
+      program.programId,
+    )[0]
+
+  const createGroup = async (
+    admin: anchor.web3.Keypair,
+    id: string,
+    payer: anchor.web3.Keypair = admin,
+  ) => {
+    const hash = createHash('sha256')
+      .update(id)
+      .update(admin.publicKey.toBuffer())
+      .digest()
+    const newGroup = anchor.utils.publicKey.findProgramAddressSync(
+      [hash, groupSeed],
+      program.programId,
+    )[0]
+
+    await program.rpc.create(
+      hash,
+      id,
+      true,
+      name,
+      encryptionKey,
+      dbType,
+      { private: {} },
+      category,
+      '',
+      [],
+      {
+        accounts: {
+          group: newGroup,
+          invitation: invitation(admin.publicKey, newGroup),
+          membershipIndex: membershipIndex(admin.publicKey),
+          membershipPage: membershipPage(admin.publicKey, 0),
+          rosterPage: rosterPage(newGroup, 0),
+          signer: admin.publicKey,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: payer == admin ? [admin] : [admin, payer],
+      },
+    )
+
+    return newGroup
+  }
+
+  const invite = async (
+    group: anchor.web3.PublicKey,
+    admin: anchor.web3.Keypair,
+    recipient: anchor.web3.PublicKey,
+    remainingAccounts: anchor.web3.AccountMeta[] = [],
+    page: number = 0,
+  ) => {
+    await program.rpc.invite(recipient, encryptionKey, {
+      accounts: {
+        newInvitation: invitation(recipient, group),
+        group,
+        invitation: invitation(admin.publicKey, group),
+        membershipIndex: membershipIndex(recipient),
+        membershipPage: membershipPage(recipient, 0),
+        rosterPage: rosterPage(group, page),
+        signer: admin.publicKey,
+        payer: admin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      remainingAccounts,
+      signers: [admin],
+    })
+  }
+
   it('Cannot create a new group with too short name', async () => {
     // Airdropping tokens to a payer.
     await provider.connection.confirmTransaction(
@@ -69,14 +231,22 @@ describe('groupchats', () => {
         newName,
         encryptionKey,
         dbType,
+        visibility,
+        category,
+        description,
+        tags,
         {
           accounts: {
             group: group[0],
             invitation: inv1[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(group[0], 0),
             signer: user1.publicKey,
             payer: user1.publicKey,
             systemProgram: SystemProgram.programId,
           },
+          remainingAccounts: directoryAccounts(category),
           signers: [user1],
         },
       )
@@ -105,14 +275,22 @@ describe('groupchats', () => {
         newName,
         encryptionKey,
         dbType,
+        visibility,
+        category,
+        description,
+        tags,
         {
           accounts: {
             group: group[0],
             invitation: inv1[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(group[0], 0),
             signer: user1.publicKey,
             payer: user1.publicKey,
             systemProgram: SystemProgram.programId,
           },
+          remainingAccounts: directoryAccounts(category),
           signers: [user1],
         },
       )
@@ -142,14 +320,22 @@ describe('groupchats', () => {
         name,
         newEncryptionKey,
         dbType,
+        visibility,
+        category,
+        description,
+        tags,
         {
           accounts: {
             group: group[0],
             invitation: inv1[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(group[0], 0),
             signer: user1.publicKey,
             payer: user1.publicKey,
             systemProgram: SystemProgram.programId,
           },
+          remainingAccounts: directoryAccounts(category),
           signers: [user1],
         },
       )
@@ -179,14 +365,22 @@ describe('groupchats', () => {
         name,
         newEncryptionKey,
         dbType,
+        visibility,
+        category,
+        description,
+        tags,
         {
           accounts: {
             group: group[0],
             invitation: inv1[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(group[0], 0),
             signer: user1.publicKey,
             payer: user1.publicKey,
             systemProgram: SystemProgram.programId,
           },
+          remainingAccounts: directoryAccounts(category),
           signers: [user1],
         },
       )
@@ -218,14 +412,22 @@ describe('groupchats', () => {
         name,
         encryptionKey,
         dbType,
+        visibility,
+        category,
+        description,
+        tags,
         {
           accounts: {
             group: group[0],
             invitation: inv1[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(group[0], 0),
             signer: user1.publicKey,
             payer: user1.publicKey,
             systemProgram: SystemProgram.programId,
           },
+          remainingAccounts: directoryAccounts(category),
           signers: [user1],
         },
       )
@@ -254,14 +456,22 @@ describe('groupchats', () => {
         name,
         encryptionKey,
         dbType,
+        visibility,
+        category,
+        description,
+        tags,
         {
           accounts: {
             group: group[0],
             invitation: inv1[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(group[0], 0),
             signer: user1.publicKey,
             payer: user1.publicKey,
             systemProgram: SystemProgram.programId,
           },
+          remainingAccounts: directoryAccounts(category),
           signers: [user1],
         },
       )
@@ -274,107 +484,212 @@ describe('groupchats', () => {
     assert.ok(failed == true)
   })
 
-  it('Creates a new group', async () => {
-    // Airdropping tokens to a payer.
-    await provider.connection.confirmTransaction(
-      await provider.connection.requestAirdrop(user1.publicKey, 10000000000),
-      'confirmed',
+  it('Cannot create a new group with a hash not derived from group id and creator', async () => {
+    const squattedHash = Buffer.from(anchor.utils.sha256.hash(groupId), 'hex')
+    const squattedGroup = anchor.utils.publicKey.findProgramAddressSync(
+      [squattedHash, groupSeed],
+      program.programId,
     )
-    await program.rpc.create(
-      groupHash,
-      groupId,
-      true,
-      name,
-      encryptionKey,
-      dbType,
-      {
-        accounts: {
-          group: group[0],
-          invitation: inv1[0],
-          signer: user1.publicKey,
-          payer: user1.publicKey,
-          systemProgram: SystemProgram.programId,
-        },
-        signers: [user1],
-      },
+    const squattedInv = anchor.utils.publicKey.findProgramAddressSync(
+      [user1.publicKey.toBytes(), squattedGroup[0].toBytes(), inviteSeed],
+      program.programId,
     )
+    let failed = false
+    try {
+      await program.rpc.create(
+        squattedHash,
+        groupId,
+        true,
+        name,
+        encryptionKey,
+        dbType,
+        visibility,
+        category,
+        description,
+        tags,
+        {
+          accounts: {
+            group: squattedGroup[0],
+            invitation: squattedInv[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(squattedGroup[0], 0),
+            signer: user1.publicKey,
+            payer: user1.publicKey,
+            systemProgram: SystemProgram.programId,
+          },
+          remainingAccounts: directoryAccounts(category),
+          signers: [user1],
+        },
+      )
+    } catch (err) {
+      const errMsg = 'Group hash does not match group id and creator'
+      assert.equal(err.toString(), errMsg)
+      failed = true
+    }
 
-    let groupAccount = await program.account.group.fetch(group[0])
-    let invitationAccount = await program.account.invitation.fetch(inv1[0])
-
-    assert.ok(groupAccount.creator.equals(user1.publicKey))
-    assert.ok(invitationAccount.sender.equals(user1.publicKey))
+    assert.ok(failed == true)
   })
 
-  it('Admin cannot invites new user if we put a wrong groupId (more then 160 characters)', async () => {
-    const newGroupId =
-      'dhfskjdfhsdjkfhsdjkfhdsjkhdjkfdhfskjdfhsdjkfhsdjkfhdsjkhdjkfdfrtt'.repeat(
-        5,
-      )
+  it('Cannot create a new group with unknown db type', async () => {
+    let failed = false
     try {
-      await program.rpc.invite(
-        newGroupId,
-        user2.publicKey,
+      await program.rpc.create(
+        groupHash,
+        groupId,
+        true,
+        name,
         encryptionKey,
-        dbType,
+        { unknown: {} },
+        visibility,
+        category,
+        description,
+        tags,
         {
           accounts: {
-            newInvitation: inv2[0],
             group: group[0],
             invitation: inv1[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(group[0], 0),
             signer: user1.publicKey,
             payer: user1.publicKey,
             systemProgram: SystemProgram.programId,
           },
+          remainingAccounts: directoryAccounts(category),
           signers: [user1],
         },
       )
     } catch (err) {
-      const errMsg = 'The field is too short or too long'
+      const errMsg = 'Database type is not supported'
       assert.equal(err.toString(), errMsg)
+      failed = true
     }
 
-    let groupAccount = await program.account.group.fetch(group[0])
-
-    assert.ok(groupAccount.members == 1)
+    assert.ok(failed == true)
   })
 
-  it('Admin cannot invites new user if we put an empty groupId', async () => {
-    const newGroupId = ''
+  it('Cannot create a new group with too many tags', async () => {
+    let failed = false
     try {
-      await program.rpc.invite(
-        newGroupId,
-        user2.publicKey,
+      await program.rpc.create(
+        groupHash,
+        groupId,
+        true,
+        name,
         encryptionKey,
         dbType,
+        visibility,
+        category,
+        description,
+        ['a', 'b', 'c', 'd', 'e', 'f'],
         {
           accounts: {
-            newInvitation: inv2[0],
             group: group[0],
             invitation: inv1[0],
+            membershipIndex: membershipIndex(user1.publicKey),
+            membershipPage: membershipPage(user1.publicKey, 0),
+            rosterPage: rosterPage(group[0], 0),
             signer: user1.publicKey,
             payer: user1.publicKey,
             systemProgram: SystemProgram.programId,
           },
+          remainingAccounts: directoryAccounts(category),
           signers: [user1],
         },
       )
     } catch (err) {
       const errMsg = 'The field is too short or too long'
       assert.equal(err.toString(), errMsg)
+      failed = true
     }
 
+    assert.ok(failed == true)
+  })
+
+  it('Creates a new group', async () => {
+    // Airdropping tokens to a payer.
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(user1.publicKey, 10000000000),
+      'confirmed',
+    )
+    let listener = null
+    const createdEvent = new Promise<any>((resolve) => {
+      listener = program.addEventListener('GroupCreated', (event) =>
+        resolve(event),
+      )
+    })
+
+    await program.rpc.create(
+      groupHash,
+      groupId,
+      true,
+      name,
+      encryptionKey,
+      dbType,
+      visibility,
+      category,
+      description,
+      tags,
+      {
+        accounts: {
+          group: group[0],
+          invitation: inv1[0],
+          membershipIndex: membershipIndex(user1.publicKey),
+          membershipPage: membershipPage(user1.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          signer: user1.publicKey,
+          payer: user1.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        remainingAccounts: directoryAccounts(category),
+        signers: [user1],
+      },
+    )
+
     let groupAccount = await program.account.group.fetch(group[0])
+    let invitationAccount = await program.account.invitation.fetch(inv1[0])
 
-    assert.ok(groupAccount.members == 1)
+    assert.ok(groupAccount.creator.equals(user1.publicKey))
+    assert.ok(groupAccount.payer.equals(user1.publicKey))
+    assert.ok(Object.keys(groupAccount.dbType)[0] == 'textile')
+    assert.ok(groupAccount.groupId == groupId)
+    assert.ok(Object.keys(groupAccount.visibility)[0] == 'public')
+    assert.ok(groupAccount.description == description)
+    assert.ok(groupAccount.tags.length == 2)
+    assert.ok(groupAccount.dbTypeVersion == 1)
+    assert.ok(invitationAccount.sender.equals(user1.publicKey))
+
+    const event = await createdEvent
+    await program.removeEventListener(listener)
+
+    assert.ok(event.group.equals(group[0]))
+    assert.ok(event.admin.equals(user1.publicKey))
+    assert.ok(Object.keys(event.dbType)[0] == 'textile')
+  })
+
+  it('Public group is listed in the directory', async () => {
+    const directoryAccount = await program.account.directory.fetch(
+      directory(category),
+    )
+    const directoryPageAccount = await program.account.directoryPage.fetch(
+      directoryPage(category, 0),
+    )
+
+    assert.ok(directoryAccount.groups == 1)
+    assert.ok(directoryPageAccount.groups.length == 1)
+    assert.ok(directoryPageAccount.groups[0].equals(group[0]))
   })
 
   it('Admin invites new user', async () => {
-    await program.rpc.invite(groupId, user2.publicKey, encryptionKey, dbType, {
+    await program.rpc.invite(user2.publicKey, encryptionKey, {
       accounts: {
         newInvitation: inv2[0],
         group: group[0],
         invitation: inv1[0],
+        membershipIndex: membershipIndex(user2.publicKey),
+        membershipPage: membershipPage(user2.publicKey, 0),
+        rosterPage: rosterPage(group[0], 0),
         signer: user1.publicKey,
         payer: user1.publicKey,
         systemProgram: SystemProgram.programId,
@@ -396,11 +711,14 @@ describe('groupchats', () => {
       'confirmed',
     )
 
-    await program.rpc.invite(groupId, user3.publicKey, encryptionKey, dbType, {
+    await program.rpc.invite(user3.publicKey, encryptionKey, {
       accounts: {
         newInvitation: inv3[0],
         group: group[0],
         invitation: inv2[0],
+        membershipIndex: membershipIndex(user3.publicKey),
+        membershipPage: membershipPage(user3.publicKey, 0),
+        rosterPage: rosterPage(group[0], 0),
         signer: user2.publicKey,
         payer: user2.publicKey,
         systemProgram: SystemProgram.programId,
@@ -415,6 +733,35 @@ describe('groupchats', () => {
     assert.ok(invitation2Account.recipient.equals(user3.publicKey))
   })
 
+  it('Membership index lists the group for every member', async () => {
+    for (const user of [user1, user2, user3]) {
+      const index = await program.account.membershipIndex.fetch(
+        membershipIndex(user.publicKey),
+      )
+      const page = await program.account.membershipPage.fetch(
+        membershipPage(user.publicKey, 0),
+      )
+
+      assert.ok(index.user.equals(user.publicKey))
+      assert.ok(index.groups == 1)
+      assert.ok(page.groups.length == 1)
+      assert.ok(page.groups[0].equals(group[0]))
+    }
+  })
+
+  it('Roster lists every member of the group', async () => {
+    const roster = await program.account.rosterPage.fetch(
+      rosterPage(group[0], 0),
+    )
+
+    assert.ok(roster.group.equals(group[0]))
+    assert.ok(roster.page == 0)
+    assert.ok(roster.members.length == 3)
+    assert.ok(roster.members[0].equals(user1.publicKey))
+    assert.ok(roster.members[1].equals(user2.publicKey))
+    assert.ok(roster.members[2].equals(user3.publicKey))
+  })
+
   it('Admin modifies group settings for open invites', async () => {
     await program.rpc.modifyOpenIvites(false, {
       accounts: {
@@ -500,23 +847,20 @@ describe('groupchats', () => {
 
   it('User now cannot invite new user', async () => {
     try {
-      await program.rpc.invite(
-        groupId,
-        user4.publicKey,
-        encryptionKey,
-        dbType,
-        {
-          accounts: {
-            newInvitation: inv4[0],
-            group: group[0],
-            invitation: inv2[0],
-            signer: user2.publicKey,
-            payer: user2.publicKey,
-            systemProgram: SystemProgram.programId,
-          },
-          signers: [user2],
+      await program.rpc.invite(user4.publicKey, encryptionKey, {
+        accounts: {
+          newInvitation: inv4[0],
+          group: group[0],
+          invitation: inv2[0],
+          membershipIndex: membershipIndex(user4.publicKey),
+          membershipPage: membershipPage(user4.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          signer: user2.publicKey,
+          payer: user2.publicKey,
+          systemProgram: SystemProgram.programId,
         },
-      )
+        signers: [user2],
+      })
       assert.ok(false)
     } catch (err) {
       const errMsg = 'User cannot perform this action'
@@ -576,11 +920,17 @@ describe('groupchats', () => {
         accounts: {
           group: group[0],
           invitation: inv2[0],
+          membershipIndex: membershipIndex(user2.publicKey),
+          membershipPage: membershipPage(user2.publicKey, 0),
+          membershipLastPage: membershipPage(user2.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          rosterLastPage: rosterPage(group[0], 0),
           successor: inv1[0],
           signer: user2.publicKey,
-          invitationSender: user1.publicKey,
+          newAdmin: user1.publicKey,
+          invitationPayer: user1.publicKey,
         },
-        signers: [user2],
+        signers: [user2, user1],
       })
       assert.ok(false)
     } catch (err) {
@@ -599,8 +949,13 @@ describe('groupchats', () => {
         accounts: {
           group: group[0],
           invitation: inv2[0],
+          membershipIndex: membershipIndex(user2.publicKey),
+          membershipPage: membershipPage(user2.publicKey, 0),
+          membershipLastPage: membershipPage(user2.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          rosterLastPage: rosterPage(group[0], 0),
           signer: user2.publicKey,
-          invitationSender: user2.publicKey,
+          invitationPayer: user2.publicKey,
         },
         signers: [user2],
       })
@@ -620,8 +975,13 @@ describe('groupchats', () => {
       accounts: {
         group: group[0],
         invitation: inv2[0],
+        membershipIndex: membershipIndex(user2.publicKey),
+        membershipPage: membershipPage(user2.publicKey, 0),
+        membershipLastPage: membershipPage(user2.publicKey, 0),
+        rosterPage: rosterPage(group[0], 0),
+        rosterLastPage: rosterPage(group[0], 0),
         signer: user2.publicKey,
-        invitationSender: user1.publicKey,
+        invitationPayer: user1.publicKey,
       },
       signers: [user2],
     })
@@ -631,12 +991,37 @@ describe('groupchats', () => {
     assert.ok(groupAccount.members == 2)
   })
 
+  it('Membership index no longer lists the group after leaving', async () => {
+    const index = await program.account.membershipIndex.fetch(
+      membershipIndex(user2.publicKey),
+    )
+    const page = await program.account.membershipPage.fetch(
+      membershipPage(user2.publicKey, 0),
+    )
+
+    assert.ok(index.groups == 0)
+    assert.ok(page.groups.length == 0)
+  })
+
+  it('Roster moves the last member into the slot of the leaving one', async () => {
+    const roster = await program.account.rosterPage.fetch(
+      rosterPage(group[0], 0),
+    )
+
+    assert.ok(roster.members.length == 2)
+    assert.ok(roster.members[0].equals(user1.publicKey))
+    assert.ok(roster.members[1].equals(user3.publicKey))
+  })
+
   it('Admin invites old user back', async () => {
-    await program.rpc.invite(groupId, user2.publicKey, encryptionKey, dbType, {
+    await program.rpc.invite(user2.publicKey, encryptionKey, {
       accounts: {
         newInvitation: inv2[0],
         group: group[0],
         invitation: inv1[0],
+        membershipIndex: membershipIndex(user2.publicKey),
+        membershipPage: membershipPage(user2.publicKey, 0),
+        rosterPage: rosterPage(group[0], 0),
         signer: user1.publicKey,
         payer: user1.publicKey,
         systemProgram: SystemProgram.programId,
@@ -657,8 +1042,13 @@ describe('groupchats', () => {
         accounts: {
           group: group[0],
           invitation: inv1[0],
+          membershipIndex: membershipIndex(user1.publicKey),
+          membershipPage: membershipPage(user1.publicKey, 0),
+          membershipLastPage: membershipPage(user1.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          rosterLastPage: rosterPage(group[0], 0),
           signer: user1.publicKey,
-          invitationSender: user1.publicKey,
+          invitationPayer: user1.publicKey,
         },
         signers: [user1],
       })
@@ -678,11 +1068,17 @@ describe('groupchats', () => {
         accounts: {
           group: group[0],
           invitation: inv1[0],
+          membershipIndex: membershipIndex(user1.publicKey),
+          membershipPage: membershipPage(user1.publicKey, 0),
+          membershipLastPage: membershipPage(user1.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          rosterLastPage: rosterPage(group[0], 0),
           successor: inv2[0],
           signer: user1.publicKey,
-          invitationSender: user2.publicKey,
+          newAdmin: user2.publicKey,
+          invitationPayer: user2.publicKey,
         },
-        signers: [user1],
+        signers: [user1, user2],
       })
       assert.ok(false)
     } catch (err) {
@@ -700,11 +1096,17 @@ describe('groupchats', () => {
       accounts: {
         group: group[0],
         invitation: inv1[0],
+        membershipIndex: membershipIndex(user1.publicKey),
+        membershipPage: membershipPage(user1.publicKey, 0),
+        membershipLastPage: membershipPage(user1.publicKey, 0),
+        rosterPage: rosterPage(group[0], 0),
+        rosterLastPage: rosterPage(group[0], 0),
         successor: inv2[0],
         signer: user1.publicKey,
-        invitationSender: user1.publicKey,
+        newAdmin: user2.publicKey,
+        invitationPayer: user1.publicKey,
       },
-      signers: [user1],
+      signers: [user1, user2],
     })
 
     let groupAccount = await program.account.group.fetch(group[0])
@@ -712,77 +1114,174 @@ describe('groupchats', () => {
     assert.ok(groupAccount.admin.equals(user2.publicKey))
   })
 
-  it('New Admin modifies group settings, changing admin', async () => {
-    await program.rpc.modifySuccessor({
-      accounts: {
-        group: group[0],
-        successor: inv3[0],
-        admin: user2.publicKey,
-      },
-      signers: [user2],
-    })
-
-    let groupAccount = await program.account.group.fetch(group[0])
-
-    assert.ok(groupAccount.members == 2)
-    assert.ok(groupAccount.admin.equals(user3.publicKey))
-  })
-
-  it('User modifies group settings, changing admin', async () => {
+  it('Admin cannot propose themselves as successor', async () => {
     try {
-      await program.rpc.modifySuccessor({
+      await program.rpc.proposeAdmin({
         accounts: {
           group: group[0],
-          successor: inv3[0],
-          admin: user1.publicKey,
+          successor: inv2[0],
+          rosterPage: rosterPage(group[0], 0),
+          admin: user2.publicKey,
         },
-        signers: [user1],
+        signers: [user2],
       })
+      assert.ok(false)
     } catch (err) {
-      const errMsg = 'User cannot perform this action'
+      const errMsg = 'Admin cannot be their own successor'
       assert.equal(err.toString(), errMsg)
     }
 
     let groupAccount = await program.account.group.fetch(group[0])
+    assert.ok(groupAccount.pendingAdmin.equals(anchor.web3.PublicKey.default))
+  })
 
-    assert.ok(groupAccount.members == 2)
-    assert.ok(groupAccount.admin.equals(user3.publicKey))
+  it('New Admin proposes a successor and cancels the proposal', async () => {
+    await program.rpc.proposeAdmin({
+      accounts: {
+        group: group[0],
+        successor: inv3[0],
+        rosterPage: rosterPage(group[0], 0),
+        admin: user2.publicKey,
+      },
+      signers: [user2],
+    })
+
+    let groupAccount = await program.account.group.fetch(group[0])
+    assert.ok(groupAccount.pendingAdmin.equals(user3.publicKey))
+    assert.ok(groupAccount.admin.equals(user2.publicKey))
+
+    await program.rpc.cancelAdminProposal({
+      accounts: {
+        group: group[0],
+        admin: user2.publicKey,
+      },
+      signers: [user2],
+    })
+
+    groupAccount = await program.account.group.fetch(group[0])
+    assert.ok(groupAccount.pendingAdmin.equals(anchor.web3.PublicKey.default))
   })
 
-  it('New Admin cannot close group if he is not the only one inside', async () => {
+  it('User cannot accept admin role without a proposal', async () => {
     try {
-      const tx = await program.rpc.close({
+      await program.rpc.acceptAdmin({
         accounts: {
           group: group[0],
           invitation: inv3[0],
+          rosterPage: rosterPage(group[0], 0),
           signer: user3.publicKey,
-          creator: user1.publicKey,
-          invitationSender: user2.publicKey,
         },
         signers: [user3],
       })
       assert.ok(false)
     } catch (err) {
-      const errMsg = 'Group not empty'
+      const errMsg = 'User is not the proposed admin'
       assert.equal(err.toString(), errMsg)
     }
 
     let groupAccount = await program.account.group.fetch(group[0])
-    assert.ok(groupAccount.members == 2)
+    assert.ok(groupAccount.admin.equals(user2.publicKey))
   })
 
-  it('Old Admin cannot close group', async () => {
-    try {
-      const tx = await program.rpc.close({
-        accounts: {
-          group: group[0],
-          invitation: inv2[0],
-          signer: user2.publicKey,
-          creator: user1.publicKey,
-          invitationSender: user1.publicKey,
-        },
-        signers: [user2],
-      })
+  it('New Admin modifies group settings, changing admin', async () => {
+    await program.rpc.proposeAdmin({
+      accounts: {
+        group: group[0],
+        successor: inv3[0],
+        rosterPage: rosterPage(group[0], 0),
+        admin: user2.publicKey,
+      },
+      signers: [user2],
+    })
+
+    let groupAccount = await program.account.group.fetch(group[0])
+    assert.ok(groupAccount.admin.equals(user2.publicKey))
+
+    await program.rpc.acceptAdmin({
+      accounts: {
+        group: group[0],
+        invitation: inv3[0],
+        rosterPage: rosterPage(group[0], 0),
+        signer: user3.publicKey,
+      },
+      signers: [user3],
+    })
+
+    groupAccount = await program.account.group.fetch(group[0])
+
+    assert.ok(groupAccount.members == 2)
+    assert.ok(groupAccount.admin.equals(user3.publicKey))
+    assert.ok(groupAccount.pendingAdmin.equals(anchor.web3.PublicKey.default))
+  })
+
+  it('User modifies group settings, changing admin', async () => {
+    try {
+      await program.rpc.proposeAdmin({
+        accounts: {
+          group: group[0],
+          successor: inv3[0],
+          rosterPage: rosterPage(group[0], 0),
+          admin: user1.publicKey,
+        },
+        signers: [user1],
+      })
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(group[0])
+
+    assert.ok(groupAccount.members == 2)
+    assert.ok(groupAccount.admin.equals(user3.publicKey))
+  })
+
+  it('New Admin cannot close group if he is not the only one inside', async () => {
+    try {
+      const tx = await program.rpc.close({
+        accounts: {
+          group: group[0],
+          invitation: inv3[0],
+          membershipIndex: membershipIndex(user3.publicKey),
+          membershipPage: membershipPage(user3.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          membershipLastPage: membershipPage(user3.publicKey, 0),
+          signer: user3.publicKey,
+          groupPayer: user1.publicKey,
+          invitationPayer: user2.publicKey,
+          rosterPagePayer: user1.publicKey,
+        },
+        remainingAccounts: listedGroupAccounts(category),
+        signers: [user3],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Group not empty'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(group[0])
+    assert.ok(groupAccount.members == 2)
+  })
+
+  it('Old Admin cannot close group', async () => {
+    try {
+      const tx = await program.rpc.close({
+        accounts: {
+          group: group[0],
+          invitation: inv2[0],
+          membershipIndex: membershipIndex(user2.publicKey),
+          membershipPage: membershipPage(user2.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          membershipLastPage: membershipPage(user2.publicKey, 0),
+          signer: user2.publicKey,
+          groupPayer: user1.publicKey,
+          invitationPayer: user1.publicKey,
+          rosterPagePayer: user1.publicKey,
+        },
+        remainingAccounts: listedGroupAccounts(category),
+        signers: [user2],
+      })
       assert.ok(false)
     } catch (err) {
       const errMsg = 'User cannot perform this action'
@@ -798,8 +1297,13 @@ describe('groupchats', () => {
       accounts: {
         group: group[0],
         invitation: inv2[0],
+        membershipIndex: membershipIndex(user2.publicKey),
+        membershipPage: membershipPage(user2.publicKey, 0),
+        membershipLastPage: membershipPage(user2.publicKey, 0),
+        rosterPage: rosterPage(group[0], 0),
+        rosterLastPage: rosterPage(group[0], 0),
         signer: user3.publicKey,
-        invitationSender: user1.publicKey,
+        invitationPayer: user1.publicKey,
       },
       signers: [user3],
     })
@@ -808,16 +1312,22 @@ describe('groupchats', () => {
     assert.ok(groupAccount.members == 1)
   })
 
-  it('Admin cannot close group giving wrong creator address', async () => {
+  it('Admin cannot close group giving wrong payer address', async () => {
     try {
       const tx = await program.rpc.close({
         accounts: {
           group: group[0],
           invitation: inv3[0],
+          membershipIndex: membershipIndex(user3.publicKey),
+          membershipPage: membershipPage(user3.publicKey, 0),
+          rosterPage: rosterPage(group[0], 0),
+          membershipLastPage: membershipPage(user3.publicKey, 0),
           signer: user3.publicKey,
-          creator: user2.publicKey,
-          invitationSender: user2.publicKey,
+          groupPayer: user2.publicKey,
+          invitationPayer: user2.publicKey,
+          rosterPagePayer: user1.publicKey,
         },
+        remainingAccounts: listedGroupAccounts(category),
         signers: [user3],
       })
       assert.ok(false)
@@ -835,13 +1345,2901 @@ describe('groupchats', () => {
       accounts: {
         group: group[0],
         invitation: inv3[0],
+        membershipIndex: membershipIndex(user3.publicKey),
+        membershipPage: membershipPage(user3.publicKey, 0),
+        rosterPage: rosterPage(group[0], 0),
+        membershipLastPage: membershipPage(user3.publicKey, 0),
         signer: user3.publicKey,
-        creator: user1.publicKey,
-        invitationSender: user2.publicKey,
+        groupPayer: user1.publicKey,
+        invitationPayer: user2.publicKey,
+        rosterPagePayer: user1.publicKey,
       },
+      remainingAccounts: listedGroupAccounts(category),
       signers: [user3],
     })
 
     assert.ok(true)
   })
+
+  it('Closed group is removed from the directory', async () => {
+    const directoryAccount = await program.account.directory.fetch(
+      directory(category),
+    )
+    const directoryPageAccount = await program.account.directoryPage.fetch(
+      directoryPage(category, 0),
+    )
+
+    assert.ok(directoryAccount.groups == 0)
+    assert.ok(directoryPageAccount.groups.length == 0)
+  })
+
+  it('Emptied directory is refunded to the payer that created it', async () => {
+    try {
+      await program.rpc.closeDirectory({
+        accounts: {
+          directory: directory(category),
+          payer: user2.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account was not created by provided user'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    const balanceBefore = await provider.connection.getBalance(
+      user1.publicKey,
+    )
+    await program.rpc.closeDirectoryPage({
+      accounts: {
+        directoryPage: directoryPage(category, 0),
+        payer: user1.publicKey,
+      },
+    })
+    await program.rpc.closeDirectory({
+      accounts: {
+        directory: directory(category),
+        payer: user1.publicKey,
+      },
+    })
+    const balanceAfter = await provider.connection.getBalance(user1.publicKey)
+
+    assert.ok(balanceAfter > balanceBefore)
+    for (const account of [directory(category), directoryPage(category, 0)]) {
+      assert.equal(await provider.connection.getAccountInfo(account), null)
+    }
+  })
+
+  const inviteLinkSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('invitelink'),
+  )
+  const linkAdmin = anchor.web3.Keypair.generate()
+  const linkUser1 = anchor.web3.Keypair.generate()
+  const linkUser2 = anchor.web3.Keypair.generate()
+  const inviteCode = 'kjsdhfkjsdhfkjsd'
+  const inviteCodeHash = Buffer.from(
+    anchor.utils.sha256.hash(inviteCode),
+    'hex',
+  )
+  let linkGroup: anchor.web3.PublicKey
+  let inviteLink: anchor.web3.PublicKey
+
+  const joinWithLink = async (user: anchor.web3.Keypair, code: string) => {
+    await program.rpc.joinWithLink(code, {
+      accounts: {
+        newInvitation: invitation(user.publicKey, linkGroup),
+        group: linkGroup,
+        inviteLink: inviteLink,
+        membershipIndex: membershipIndex(user.publicKey),
+        membershipPage: membershipPage(user.publicKey, 0),
+        rosterPage: rosterPage(linkGroup, 0),
+        signer: user.publicKey,
+        payer: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [user],
+    })
+  }
+
+  it('Admin creates an invite link', async () => {
+    await airdrop(linkAdmin)
+    await airdrop(linkUser1)
+    await airdrop(linkUser2)
+    linkGroup = await createGroup(linkAdmin, 'linkgroup')
+    inviteLink = anchor.utils.publicKey.findProgramAddressSync(
+      [linkGroup.toBuffer(), inviteCodeHash, inviteLinkSeed],
+      program.programId,
+    )[0]
+    const expiresAt = new anchor.BN(Math.floor(Date.now() / 1000) + 3600)
+
+    await program.rpc.createInviteLink(inviteCodeHash, 1, expiresAt, {
+      accounts: {
+        inviteLink: inviteLink,
+        group: linkGroup,
+        admin: linkAdmin.publicKey,
+        payer: linkAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [linkAdmin],
+    })
+
+    let inviteLinkAccount = await program.account.inviteLink.fetch(inviteLink)
+    assert.ok(inviteLinkAccount.group.equals(linkGroup))
+    assert.ok(inviteLinkAccount.maxUses == 1)
+    assert.ok(inviteLinkAccount.uses == 0)
+  })
+
+  it('User cannot join with a wrong invite code', async () => {
+    try {
+      await joinWithLink(linkUser1, 'sdlkfjsdlkfjsdlkfj')
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Invite code does not match the link'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(linkGroup)
+    assert.ok(groupAccount.members == 1)
+  })
+
+  it('User joins with invite link', async () => {
+    await joinWithLink(linkUser1, inviteCode)
+
+    let groupAccount = await program.account.group.fetch(linkGroup)
+    let inviteLinkAccount = await program.account.inviteLink.fetch(inviteLink)
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(linkUser1.publicKey, linkGroup),
+    )
+
+    assert.ok(groupAccount.members == 2)
+    assert.ok(inviteLinkAccount.uses == 1)
+    assert.ok(invitationAccount.recipient.equals(linkUser1.publicKey))
+    assert.ok(invitationAccount.encryptionKey == '')
+  })
+
+  it('Admin cannot propose a successor without the encryption key', async () => {
+    try {
+      await program.rpc.proposeAdmin({
+        accounts: {
+          group: linkGroup,
+          successor: invitation(linkUser1.publicKey, linkGroup),
+          rosterPage: rosterPage(linkGroup, 0),
+          admin: linkAdmin.publicKey,
+        },
+        signers: [linkAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Successor is not an active member of the group'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(linkGroup)
+    assert.ok(groupAccount.pendingAdmin.equals(anchor.web3.PublicKey.default))
+  })
+
+  it('Admin cannot propose a member of another group as successor', async () => {
+    const otherGroup = await createGroup(linkUser2, 'othergroup')
+    try {
+      await program.rpc.proposeAdmin({
+        accounts: {
+          group: linkGroup,
+          successor: invitation(linkUser2.publicKey, otherGroup),
+          rosterPage: rosterPage(linkGroup, 0),
+          admin: linkAdmin.publicKey,
+        },
+        signers: [linkAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'A seeds constraint was violated'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(linkGroup)
+    assert.ok(groupAccount.pendingAdmin.equals(anchor.web3.PublicKey.default))
+  })
+
+  it('User cannot join with an exhausted invite link', async () => {
+    try {
+      await joinWithLink(linkUser2, inviteCode)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Invite link has no uses left'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(linkGroup)
+    assert.ok(groupAccount.members == 2)
+  })
+
+  it('Admin delivers encryption key to user who joined with link', async () => {
+    await program.rpc.deliverKey(encryptionKey, {
+      accounts: {
+        group: linkGroup,
+        invitation: invitation(linkAdmin.publicKey, linkGroup),
+        recipientInvitation: invitation(linkUser1.publicKey, linkGroup),
+        signer: linkAdmin.publicKey,
+      },
+      signers: [linkAdmin],
+    })
+
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(linkUser1.publicKey, linkGroup),
+    )
+    assert.ok(invitationAccount.encryptionKey == encryptionKey)
+  })
+
+  it('Encryption key cannot be delivered twice', async () => {
+    try {
+      await program.rpc.deliverKey(encryptionKey, {
+        accounts: {
+          group: linkGroup,
+          invitation: invitation(linkAdmin.publicKey, linkGroup),
+          recipientInvitation: invitation(linkUser1.publicKey, linkGroup),
+          signer: linkAdmin.publicKey,
+        },
+        signers: [linkAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Encryption key already delivered'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Admin closes invite link', async () => {
+    await program.rpc.closeInviteLink({
+      accounts: {
+        inviteLink: inviteLink,
+        group: linkGroup,
+        admin: linkAdmin.publicKey,
+        payer: linkAdmin.publicKey,
+      },
+      signers: [linkAdmin],
+    })
+
+    let inviteLinkAccount = await program.account.inviteLink.fetchNullable(
+      inviteLink,
+    )
+    assert.ok(inviteLinkAccount == null)
+  })
+
+  const joinRequestSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('joinrequest'),
+  )
+  const joinAdmin = anchor.web3.Keypair.generate()
+  const joinUser = anchor.web3.Keypair.generate()
+  let joinGroup: anchor.web3.PublicKey
+  let joinRequest: anchor.web3.PublicKey
+
+  const requestToJoin = async () => {
+    await program.rpc.requestToJoin({
+      accounts: {
+        joinRequest: joinRequest,
+        group: joinGroup,
+        signer: joinUser.publicKey,
+        payer: joinUser.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [joinUser],
+    })
+  }
+
+  it('User requests to join a group', async () => {
+    await airdrop(joinAdmin)
+    await airdrop(joinUser)
+    joinGroup = await createGroup(joinAdmin, 'joingroup')
+    joinRequest = anchor.utils.publicKey.findProgramAddressSync(
+      [joinUser.publicKey.toBytes(), joinGroup.toBytes(), joinRequestSeed],
+      program.programId,
+    )[0]
+
+    await requestToJoin()
+
+    let joinRequestAccount = await program.account.joinRequest.fetch(
+      joinRequest,
+    )
+    assert.ok(joinRequestAccount.group.equals(joinGroup))
+    assert.ok(joinRequestAccount.requester.equals(joinUser.publicKey))
+  })
+
+  it('User cannot approve his own join request', async () => {
+    try {
+      await program.rpc.approveJoin(encryptionKey, {
+        accounts: {
+          newInvitation: invitation(joinUser.publicKey, joinGroup),
+          group: joinGroup,
+          joinRequest: joinRequest,
+          membershipIndex: membershipIndex(joinUser.publicKey),
+          membershipPage: membershipPage(joinUser.publicKey, 0),
+          rosterPage: rosterPage(joinGroup, 0),
+          admin: joinUser.publicKey,
+          payer: joinUser.publicKey,
+          joinRequestPayer: joinUser.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [joinUser],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(joinGroup)
+    assert.ok(groupAccount.members == 1)
+  })
+
+  const cancelJoinRequest = async (
+    request: anchor.web3.PublicKey,
+    group: anchor.web3.PublicKey,
+    signer: anchor.web3.Keypair,
+  ) => {
+    await program.rpc.cancelJoinRequest({
+      accounts: {
+        group,
+        joinRequest: request,
+        signer: signer.publicKey,
+        joinRequestPayer: joinUser.publicKey,
+      },
+      signers: [signer],
+    })
+  }
+
+  it('Only the requester cancels a join request to a live group', async () => {
+    try {
+      await cancelJoinRequest(joinRequest, joinGroup, joinAdmin)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    await cancelJoinRequest(joinRequest, joinGroup, joinUser)
+
+    let joinRequestAccount = await program.account.joinRequest.fetchNullable(
+      joinRequest,
+    )
+    assert.ok(joinRequestAccount == null)
+  })
+
+  it('Admin rejects join request', async () => {
+    await requestToJoin()
+
+    await program.rpc.rejectJoin({
+      accounts: {
+        group: joinGroup,
+        joinRequest: joinRequest,
+        admin: joinAdmin.publicKey,
+        joinRequestPayer: joinUser.publicKey,
+      },
+      signers: [joinAdmin],
+    })
+
+    let joinRequestAccount = await program.account.joinRequest.fetchNullable(
+      joinRequest,
+    )
+    let groupAccount = await program.account.group.fetch(joinGroup)
+    assert.ok(joinRequestAccount == null)
+    assert.ok(groupAccount.members == 1)
+  })
+
+  it('Admin approves join request', async () => {
+    await requestToJoin()
+
+    await program.rpc.approveJoin(encryptionKey, {
+      accounts: {
+        newInvitation: invitation(joinUser.publicKey, joinGroup),
+        group: joinGroup,
+        joinRequest: joinRequest,
+        membershipIndex: membershipIndex(joinUser.publicKey),
+        membershipPage: membershipPage(joinUser.publicKey, 0),
+        rosterPage: rosterPage(joinGroup, 0),
+        admin: joinAdmin.publicKey,
+        payer: joinAdmin.publicKey,
+        joinRequestPayer: joinUser.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [joinAdmin],
+    })
+
+    let joinRequestAccount = await program.account.joinRequest.fetchNullable(
+      joinRequest,
+    )
+    let groupAccount = await program.account.group.fetch(joinGroup)
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(joinUser.publicKey, joinGroup),
+    )
+    assert.ok(joinRequestAccount == null)
+    assert.ok(groupAccount.members == 2)
+    assert.ok(invitationAccount.recipient.equals(joinUser.publicKey))
+    assert.ok(invitationAccount.encryptionKey == encryptionKey)
+  })
+
+  const dissolveAdmin = anchor.web3.Keypair.generate()
+  const dissolveUser1 = anchor.web3.Keypair.generate()
+  const dissolveUser2 = anchor.web3.Keypair.generate()
+  const cranker = anchor.web3.Keypair.generate()
+  let dissolveGroup: anchor.web3.PublicKey
+
+  const releaseInvitation = async (user: anchor.web3.PublicKey) => {
+    await program.rpc.leave({
+      accounts: {
+        group: dissolveGroup,
+        invitation: invitation(user, dissolveGroup),
+        membershipIndex: membershipIndex(user),
+        membershipPage: membershipPage(user, 0),
+        membershipLastPage: membershipPage(user, 0),
+        rosterPage: rosterPage(dissolveGroup, 0),
+        rosterLastPage: rosterPage(dissolveGroup, 0),
+        signer: cranker.publicKey,
+        invitationPayer: dissolveAdmin.publicKey,
+      },
+      signers: [cranker],
+    })
+  }
+
+  const closeDissolved = async () => {
+    await program.rpc.closeDissolved({
+      accounts: {
+        group: dissolveGroup,
+        rosterPage: rosterPage(dissolveGroup, 0),
+        groupPayer: dissolveAdmin.publicKey,
+        rosterPagePayer: dissolveAdmin.publicKey,
+      },
+    })
+  }
+
+  it('Stranger cannot close invitations of a live group', async () => {
+    await airdrop(dissolveAdmin)
+    await airdrop(cranker)
+    dissolveGroup = await createGroup(dissolveAdmin, 'dissolvegroup')
+    await invite(dissolveGroup, dissolveAdmin, dissolveUser1.publicKey)
+    await invite(dissolveGroup, dissolveAdmin, dissolveUser2.publicKey)
+
+    try {
+      await releaseInvitation(dissolveUser2.publicKey)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(dissolveGroup)
+    assert.ok(groupAccount.members == 3)
+  })
+
+  // an invite link, a channel and pins that the group leaves behind
+  const leftoverLink = () =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [dissolveGroup.toBuffer(), inviteCodeHash, inviteLinkSeed],
+      program.programId,
+    )[0]
+
+  const leftoverJoinRequest = () =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [joinUser.publicKey.toBytes(), dissolveGroup.toBytes(), joinRequestSeed],
+      program.programId,
+    )[0]
+
+  const reapLeftovers = async () => {
+    await cancelJoinRequest(leftoverJoinRequest(), dissolveGroup, cranker)
+    await program.rpc.reapInviteLink({
+      accounts: {
+        inviteLink: leftoverLink(),
+        group: dissolveGroup,
+        payer: dissolveAdmin.publicKey,
+      },
+    })
+    await program.rpc.reapChannel({
+      accounts: {
+        channel: channelOf(dissolveGroup, channelId),
+        group: dissolveGroup,
+        payer: dissolveAdmin.publicKey,
+      },
+    })
+    await program.rpc.closePins({
+      accounts: {
+        pins: pinsOf(dissolveGroup),
+        group: dissolveGroup,
+        payer: dissolveAdmin.publicKey,
+      },
+    })
+  }
+
+  it('Leftovers of a live group cannot be reaped', async () => {
+    await program.rpc.requestToJoin({
+      accounts: {
+        joinRequest: leftoverJoinRequest(),
+        group: dissolveGroup,
+        signer: joinUser.publicKey,
+        payer: joinUser.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [joinUser],
+    })
+    const expiresAt = new anchor.BN(Math.floor(Date.now() / 1000) + 3600)
+    await program.rpc.createInviteLink(inviteCodeHash, 1, expiresAt, {
+      accounts: {
+        inviteLink: leftoverLink(),
+        group: dissolveGroup,
+        admin: dissolveAdmin.publicKey,
+        payer: dissolveAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [dissolveAdmin],
+    })
+    await program.rpc.createChannel(
+      channelId,
+      'general',
+      '',
+      { member: {} },
+      {
+        accounts: {
+          channel: channelOf(dissolveGroup, channelId),
+          group: dissolveGroup,
+          invitation: invitation(dissolveAdmin.publicKey, dissolveGroup),
+          signer: dissolveAdmin.publicKey,
+          payer: dissolveAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [dissolveAdmin],
+      },
+    )
+    await program.rpc.pin(pinnedHash, {
+      accounts: {
+        pins: pinsOf(dissolveGroup),
+        group: dissolveGroup,
+        invitation: invitation(dissolveAdmin.publicKey, dissolveGroup),
+        signer: dissolveAdmin.publicKey,
+        payer: dissolveAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [dissolveAdmin],
+    })
+
+    try {
+      await reapLeftovers()
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+    try {
+      await program.rpc.reapInviteLink({
+        accounts: {
+          inviteLink: leftoverLink(),
+          group: dissolveGroup,
+          payer: dissolveAdmin.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Group is still active'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Admin proposes a rename before dissolving the group', async () => {
+    const deadline = new anchor.BN(Math.floor(Date.now() / 1000) + 3600)
+    await program.rpc.createProposal(
+      { rename: { name: 'Dissolved group' } },
+      deadline,
+      {
+        accounts: {
+          proposal: proposalOf(dissolveGroup, 0),
+          group: dissolveGroup,
+          invitation: invitation(dissolveAdmin.publicKey, dissolveGroup),
+          signer: dissolveAdmin.publicKey,
+          payer: dissolveAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [dissolveAdmin],
+      },
+    )
+  })
+
+  it('User cannot dissolve a group', async () => {
+    try {
+      await program.rpc.dissolve({
+        accounts: {
+          group: dissolveGroup,
+          admin: cranker.publicKey,
+        },
+        signers: [cranker],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(dissolveGroup)
+    assert.ok(!groupAccount.dissolved)
+  })
+
+  it('Admin dissolves a group with members', async () => {
+    await program.rpc.dissolve({
+      accounts: {
+        group: dissolveGroup,
+        admin: dissolveAdmin.publicKey,
+      },
+      signers: [dissolveAdmin],
+    })
+
+    let groupAccount = await program.account.group.fetch(dissolveGroup)
+    assert.ok(groupAccount.dissolved)
+    assert.ok(groupAccount.members == 3)
+  })
+
+  it('Members cannot vote in a dissolved group', async () => {
+    const proposal = proposalOf(dissolveGroup, 0)
+    const memberInvitation = invitation(dissolveAdmin.publicKey, dissolveGroup)
+    try {
+      await program.rpc.castVote(true, {
+        accounts: {
+          vote: anchor.utils.publicKey.findProgramAddressSync(
+            [proposal.toBuffer(), memberInvitation.toBuffer(), voteSeed],
+            program.programId,
+          )[0],
+          proposal,
+          group: dissolveGroup,
+          invitation: memberInvitation,
+          signer: dissolveAdmin.publicKey,
+          payer: dissolveAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [dissolveAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Group has been dissolved'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Nobody can be invited to a dissolved group', async () => {
+    try {
+      await invite(dissolveGroup, dissolveAdmin, cranker.publicKey)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Group has been dissolved'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(dissolveGroup)
+    assert.ok(groupAccount.members == 3)
+  })
+
+  it('Dissolved group cannot be closed while invitations remain', async () => {
+    try {
+      await closeDissolved()
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Group not empty'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Anyone closes the invitations of a dissolved group', async () => {
+    const balanceBefore = await provider.connection.getBalance(
+      dissolveAdmin.publicKey,
+    )
+
+    await releaseInvitation(dissolveUser2.publicKey)
+    await releaseInvitation(dissolveUser1.publicKey)
+    await releaseInvitation(dissolveAdmin.publicKey)
+
+    const balanceAfter = await provider.connection.getBalance(
+      dissolveAdmin.publicKey,
+    )
+    let groupAccount = await program.account.group.fetch(dissolveGroup)
+    let invitationAccount = await program.account.invitation.fetchNullable(
+      invitation(dissolveUser1.publicKey, dissolveGroup),
+    )
+    assert.ok(groupAccount.members == 0)
+    assert.ok(invitationAccount == null)
+    assert.ok(balanceAfter > balanceBefore)
+  })
+
+  it('Dissolved group closes once the last invitation is gone', async () => {
+    await closeDissolved()
+
+    let groupAccount = await program.account.group.fetchNullable(dissolveGroup)
+    assert.ok(groupAccount == null)
+  })
+
+  it('Anyone closes the leftovers of a closed group', async () => {
+    await reapLeftovers()
+
+    for (const account of [
+      leftoverJoinRequest(),
+      leftoverLink(),
+      channelOf(dissolveGroup, channelId),
+      pinsOf(dissolveGroup),
+    ]) {
+      assert.equal(await provider.connection.getAccountInfo(account), null)
+    }
+  })
+
+  const reapAdmin = anchor.web3.Keypair.generate()
+  const reapUser = anchor.web3.Keypair.generate()
+  let reapGroup: anchor.web3.PublicKey
+
+  const reapInvitation = async (claimReward: boolean) => {
+    await program.rpc.reapInvitation(claimReward, {
+      accounts: {
+        invitation: invitation(reapUser.publicKey, reapGroup),
+        group: reapGroup,
+        membershipIndex: membershipIndex(reapUser.publicKey),
+        membershipPage: membershipPage(reapUser.publicKey, 0),
+        membershipLastPage: membershipPage(reapUser.publicKey, 0),
+        invitationPayer: reapAdmin.publicKey,
+        cranker: cranker.publicKey,
+      },
+      remainingAccounts: [
+        {
+          pubkey: rosterPage(reapGroup, 0),
+          isWritable: true,
+          isSigner: false,
+        },
+        {
+          pubkey: rosterPage(reapGroup, 0),
+          isWritable: true,
+          isSigner: false,
+        },
+      ],
+      signers: [cranker],
+    })
+  }
+
+  it('Invitation of an active group cannot be reaped', async () => {
+    await airdrop(reapAdmin)
+    reapGroup = await createGroup(reapAdmin, 'reapgroup')
+    await invite(reapGroup, reapAdmin, reapUser.publicKey)
+
+    try {
+      await reapInvitation(true)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Group is still active'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(reapGroup)
+    assert.ok(groupAccount.members == 2)
+  })
+
+  it('Anyone reaps the invitation of a dissolved group for a reward', async () => {
+    await program.rpc.dissolve({
+      accounts: {
+        group: reapGroup,
+        admin: reapAdmin.publicKey,
+      },
+      signers: [reapAdmin],
+    })
+    const balanceBefore = await provider.connection.getBalance(
+      cranker.publicKey,
+    )
+
+    await reapInvitation(true)
+
+    const balanceAfter = await provider.connection.getBalance(cranker.publicKey)
+    let groupAccount = await program.account.group.fetch(reapGroup)
+    let rosterAccount = await program.account.rosterPage.fetch(
+      rosterPage(reapGroup, 0),
+    )
+    let invitationAccount = await program.account.invitation.fetchNullable(
+      invitation(reapUser.publicKey, reapGroup),
+    )
+    let membershipIndexAccount = await program.account.membershipIndex.fetch(
+      membershipIndex(reapUser.publicKey),
+    )
+    assert.ok(groupAccount.members == 1)
+    assert.ok(rosterAccount.members.length == 1)
+    assert.ok(invitationAccount == null)
+    assert.ok(membershipIndexAccount.groups == 0)
+    assert.ok(balanceAfter == balanceBefore + 5000)
+  })
+
+  const profileAdmin = anchor.web3.Keypair.generate()
+  const profileModerator = anchor.web3.Keypair.generate()
+  const profileUser = anchor.web3.Keypair.generate()
+  const avatarHash =
+    'QmSJdkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdh'
+  const topic = 'Weekly community call on friday'
+  let profileGroup: anchor.web3.PublicKey
+
+  it('Admin sets the group avatar', async () => {
+    await airdrop(profileAdmin)
+    profileGroup = await createGroup(profileAdmin, 'profilegroup')
+    await invite(profileGroup, profileAdmin, profileModerator.publicKey)
+    await invite(profileGroup, profileAdmin, profileUser.publicKey)
+
+    await program.rpc.setAvatarHash(avatarHash, {
+      accounts: {
+        group: profileGroup,
+        invitation: invitation(profileAdmin.publicKey, profileGroup),
+        signer: profileAdmin.publicKey,
+      },
+      signers: [profileAdmin],
+    })
+
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(groupAccount.avatarHash == avatarHash)
+    assert.ok(groupAccount.topic == '')
+  })
+
+  it('Admin cannot set an avatar hash with the wrong length', async () => {
+    try {
+      await program.rpc.setAvatarHash('QmSJdkjsdhfkjsdhf', {
+        accounts: {
+          group: profileGroup,
+          invitation: invitation(profileAdmin.publicKey, profileGroup),
+          signer: profileAdmin.publicKey,
+        },
+        signers: [profileAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'The field is too short or too long'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(groupAccount.avatarHash == avatarHash)
+  })
+
+  it('Member cannot set the group topic', async () => {
+    try {
+      await program.rpc.setTopic(topic, {
+        accounts: {
+          group: profileGroup,
+          invitation: invitation(profileUser.publicKey, profileGroup),
+          signer: profileUser.publicKey,
+        },
+        signers: [profileUser],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(groupAccount.topic == '')
+  })
+
+  it('Member cannot promote themselves', async () => {
+    try {
+      await program.rpc.setRole(
+        { moderator: {} },
+        {
+          accounts: {
+            group: profileGroup,
+            invitation: invitation(profileUser.publicKey, profileGroup),
+            admin: profileUser.publicKey,
+          },
+          signers: [profileUser],
+        },
+      )
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(profileUser.publicKey, profileGroup),
+    )
+    assert.ok(invitationAccount.role.member)
+  })
+
+  it('Moderator sets the group topic and description', async () => {
+    await program.rpc.setRole(
+      { moderator: {} },
+      {
+        accounts: {
+          group: profileGroup,
+          invitation: invitation(profileModerator.publicKey, profileGroup),
+          admin: profileAdmin.publicKey,
+        },
+        signers: [profileAdmin],
+      },
+    )
+
+    await program.rpc.setTopic(topic, {
+      accounts: {
+        group: profileGroup,
+        invitation: invitation(profileModerator.publicKey, profileGroup),
+        signer: profileModerator.publicKey,
+      },
+      signers: [profileModerator],
+    })
+    await program.rpc.setDescription(description, {
+      accounts: {
+        group: profileGroup,
+        invitation: invitation(profileModerator.publicKey, profileGroup),
+        signer: profileModerator.publicKey,
+      },
+      signers: [profileModerator],
+    })
+
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(profileModerator.publicKey, profileGroup),
+    )
+    assert.ok(invitationAccount.role.moderator)
+    assert.ok(groupAccount.topic == topic)
+    assert.ok(groupAccount.description == description)
+  })
+
+  const pinsSeed = Buffer.from(anchor.utils.bytes.utf8.encode('pins'))
+  const pinnedHash =
+    'QmPinkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdhfkjsdh'
+
+  const pinsOf = (group: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [group.toBuffer(), pinsSeed],
+      program.programId,
+    )[0]
+
+  it('Member cannot pin an item', async () => {
+    try {
+      await program.rpc.pin(pinnedHash, {
+        accounts: {
+          pins: pinsOf(profileGroup),
+          group: profileGroup,
+          invitation: invitation(profileUser.publicKey, profileGroup),
+          signer: profileUser.publicKey,
+          payer: profileAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [profileUser, profileAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Moderator pins an item', async () => {
+    let listener = null
+    const pinnedEvent = new Promise<any>((resolve) => {
+      listener = program.addEventListener('PinAdded', (event) => resolve(event))
+    })
+
+    await program.rpc.pin(pinnedHash, {
+      accounts: {
+        pins: pinsOf(profileGroup),
+        group: profileGroup,
+        invitation: invitation(profileModerator.publicKey, profileGroup),
+        signer: profileModerator.publicKey,
+        payer: profileAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [profileModerator, profileAdmin],
+    })
+
+    let pinsAccount = await program.account.pins.fetch(pinsOf(profileGroup))
+    assert.ok(pinsAccount.group.equals(profileGroup))
+    assert.ok(pinsAccount.items.length == 1)
+    assert.ok(pinsAccount.items[0] == pinnedHash)
+    assert.ok(pinsAccount.payer.equals(profileAdmin.publicKey))
+
+    const event = await pinnedEvent
+    await program.removeEventListener(listener)
+
+    assert.ok(event.group.equals(profileGroup))
+    assert.ok(event.contentHash == pinnedHash)
+    assert.ok(event.pinnedBy.equals(profileModerator.publicKey))
+  })
+
+  it('Item cannot be pinned twice', async () => {
+    try {
+      await program.rpc.pin(pinnedHash, {
+        accounts: {
+          pins: pinsOf(profileGroup),
+          group: profileGroup,
+          invitation: invitation(profileAdmin.publicKey, profileGroup),
+          signer: profileAdmin.publicKey,
+          payer: profileAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [profileAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Item is already pinned'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Admin unpins an item', async () => {
+    let listener = null
+    const unpinnedEvent = new Promise<any>((resolve) => {
+      listener = program.addEventListener('PinRemoved', (event) =>
+        resolve(event),
+      )
+    })
+
+    await program.rpc.unpin(pinnedHash, {
+      accounts: {
+        pins: pinsOf(profileGroup),
+        group: profileGroup,
+        invitation: invitation(profileAdmin.publicKey, profileGroup),
+        signer: profileAdmin.publicKey,
+      },
+      signers: [profileAdmin],
+    })
+
+    let pinsAccount = await program.account.pins.fetch(pinsOf(profileGroup))
+    assert.ok(pinsAccount.items.length == 0)
+
+    const event = await unpinnedEvent
+    await program.removeEventListener(listener)
+
+    assert.ok(event.contentHash == pinnedHash)
+    assert.ok(event.unpinnedBy.equals(profileAdmin.publicKey))
+  })
+
+  it('Item that is not pinned cannot be unpinned', async () => {
+    try {
+      await program.rpc.unpin(pinnedHash, {
+        accounts: {
+          pins: pinsOf(profileGroup),
+          group: profileGroup,
+          invitation: invitation(profileAdmin.publicKey, profileGroup),
+          signer: profileAdmin.publicKey,
+        },
+        signers: [profileAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Item is not pinned'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Emptied pins are refunded to their payer', async () => {
+    await program.rpc.closePins({
+      accounts: {
+        pins: pinsOf(profileGroup),
+        group: profileGroup,
+        payer: profileAdmin.publicKey,
+      },
+    })
+
+    assert.equal(
+      await provider.connection.getAccountInfo(pinsOf(profileGroup)),
+      null,
+    )
+  })
+
+  const channelSeed = Buffer.from(anchor.utils.bytes.utf8.encode('channel'))
+  const channelId = 1
+
+  const channelOf = (group: anchor.web3.PublicKey, id: number) => {
+    const idBuffer = Buffer.alloc(2)
+    idBuffer.writeUInt16LE(id)
+    return anchor.utils.publicKey.findProgramAddressSync(
+      [group.toBuffer(), idBuffer, channelSeed],
+      program.programId,
+    )[0]
+  }
+
+  it('Member cannot create a channel', async () => {
+    try {
+      await program.rpc.createChannel(
+        channelId,
+        'development',
+        '',
+        { moderator: {} },
+        {
+          accounts: {
+            channel: channelOf(profileGroup, channelId),
+            group: profileGroup,
+            invitation: invitation(profileUser.publicKey, profileGroup),
+            signer: profileUser.publicKey,
+            payer: profileAdmin.publicKey,
+            systemProgram: SystemProgram.programId,
+          },
+          signers: [profileUser, profileAdmin],
+        },
+      )
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Moderator creates a channel restricted to moderators', async () => {
+    await program.rpc.createChannel(
+      channelId,
+      'development',
+      encryptionKey,
+      { moderator: {} },
+      {
+        accounts: {
+          channel: channelOf(profileGroup, channelId),
+          group: profileGroup,
+          invitation: invitation(profileModerator.publicKey, profileGroup),
+          signer: profileModerator.publicKey,
+          payer: profileAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [profileModerator, profileAdmin],
+      },
+    )
+
+    let channelAccount = await program.account.channel.fetch(
+      channelOf(profileGroup, channelId),
+    )
+    assert.ok(channelAccount.group.equals(profileGroup))
+    assert.ok(channelAccount.channelId == channelId)
+    assert.ok(channelAccount.name == 'development')
+    assert.ok(channelAccount.encryptionKey == encryptionKey)
+    assert.ok(channelAccount.minRole.moderator)
+  })
+
+  it('Moderator renames a channel and opens it to members', async () => {
+    await program.rpc.renameChannel('general', {
+      accounts: {
+        channel: channelOf(profileGroup, channelId),
+        group: profileGroup,
+        invitation: invitation(profileModerator.publicKey, profileGroup),
+        signer: profileModerator.publicKey,
+      },
+      signers: [profileModerator],
+    })
+    await program.rpc.setChannelRole(
+      { member: {} },
+      {
+        accounts: {
+          channel: channelOf(profileGroup, channelId),
+          group: profileGroup,
+          invitation: invitation(profileModerator.publicKey, profileGroup),
+          signer: profileModerator.publicKey,
+        },
+        signers: [profileModerator],
+      },
+    )
+
+    let channelAccount = await program.account.channel.fetch(
+      channelOf(profileGroup, channelId),
+    )
+    assert.ok(channelAccount.name == 'general')
+    assert.ok(channelAccount.minRole.member)
+  })
+
+  it('Member cannot delete a channel', async () => {
+    try {
+      await program.rpc.deleteChannel({
+        accounts: {
+          channel: channelOf(profileGroup, channelId),
+          group: profileGroup,
+          invitation: invitation(profileUser.publicKey, profileGroup),
+          signer: profileUser.publicKey,
+          payer: profileAdmin.publicKey,
+        },
+        signers: [profileUser],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Moderator deletes a channel', async () => {
+    await program.rpc.deleteChannel({
+      accounts: {
+        channel: channelOf(profileGroup, channelId),
+        group: profileGroup,
+        invitation: invitation(profileModerator.publicKey, profileGroup),
+        signer: profileModerator.publicKey,
+        payer: profileAdmin.publicKey,
+      },
+      signers: [profileModerator],
+    })
+
+    let channelAccount = await program.account.channel.fetchNullable(
+      channelOf(profileGroup, channelId),
+    )
+    assert.ok(channelAccount == null)
+  })
+
+  it('Moderator mutes a member', async () => {
+    const mutedUntil = new anchor.BN(Math.floor(Date.now() / 1000) + 3600)
+
+    await program.rpc.mute(mutedUntil, {
+      accounts: {
+        group: profileGroup,
+        invitation: invitation(profileModerator.publicKey, profileGroup),
+        memberInvitation: invitation(profileUser.publicKey, profileGroup),
+        rosterPage: rosterPage(profileGroup, 0),
+        signer: profileModerator.publicKey,
+      },
+      signers: [profileModerator],
+    })
+
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(profileUser.publicKey, profileGroup),
+    )
+    assert.ok(invitationAccount.mutedUntil.eq(mutedUntil))
+  })
+
+  it('Moderator cannot mute the admin', async () => {
+    try {
+      await program.rpc.mute(new anchor.BN(1), {
+        accounts: {
+          group: profileGroup,
+          invitation: invitation(profileModerator.publicKey, profileGroup),
+          memberInvitation: invitation(profileAdmin.publicKey, profileGroup),
+          rosterPage: rosterPage(profileGroup, 0),
+          signer: profileModerator.publicKey,
+        },
+        signers: [profileModerator],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(profileAdmin.publicKey, profileGroup),
+    )
+    assert.ok(invitationAccount.mutedUntil.eqn(0))
+  })
+
+  it('Member cannot mute another member', async () => {
+    try {
+      await program.rpc.mute(new anchor.BN(0), {
+        accounts: {
+          group: profileGroup,
+          invitation: invitation(profileUser.publicKey, profileGroup),
+          memberInvitation: invitation(profileModerator.publicKey, profileGroup),
+          rosterPage: rosterPage(profileGroup, 0),
+          signer: profileUser.publicKey,
+        },
+        signers: [profileUser],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  const setProfileRole = async (
+    member: anchor.web3.Keypair,
+    role: { member: {} } | { moderator: {} },
+  ) => {
+    await program.rpc.setRole(role, {
+      accounts: {
+        group: profileGroup,
+        invitation: invitation(member.publicKey, profileGroup),
+        admin: profileAdmin.publicKey,
+      },
+      signers: [profileAdmin],
+    })
+  }
+
+  it('Only the admin mutes a moderator', async () => {
+    await setProfileRole(profileUser, { moderator: {} })
+    const muteModerator = async (signer: anchor.web3.Keypair) => {
+      await program.rpc.mute(new anchor.BN(1), {
+        accounts: {
+          group: profileGroup,
+          invitation: invitation(signer.publicKey, profileGroup),
+          memberInvitation: invitation(
+            profileModerator.publicKey,
+            profileGroup,
+          ),
+          rosterPage: rosterPage(profileGroup, 0),
+          signer: signer.publicKey,
+        },
+        signers: [signer],
+      })
+    }
+
+    try {
+      await muteModerator(profileUser)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+    await muteModerator(profileAdmin)
+    await setProfileRole(profileUser, { member: {} })
+
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(profileModerator.publicKey, profileGroup),
+    )
+    assert.ok(invitationAccount.mutedUntil.eqn(1))
+  })
+
+  it('Member has to be on the roster of the group to be muted', async () => {
+    try {
+      await program.rpc.mute(new anchor.BN(1), {
+        accounts: {
+          group: profileGroup,
+          invitation: invitation(profileModerator.publicKey, profileGroup),
+          memberInvitation: invitation(profileUser.publicKey, profileGroup),
+          rosterPage: rosterPage(linkGroup, 0),
+          signer: profileModerator.publicKey,
+        },
+        signers: [profileModerator],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Index does not match the provided accounts'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Admin switches the group to announcement only', async () => {
+    await program.rpc.modifyAnnouncementOnly(true, {
+      accounts: {
+        group: profileGroup,
+        admin: profileAdmin.publicKey,
+      },
+      signers: [profileAdmin],
+    })
+
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(groupAccount.announcementOnly)
+  })
+
+  it('Moderator cannot switch the group to announcement only', async () => {
+    try {
+      await program.rpc.modifyAnnouncementOnly(false, {
+        accounts: {
+          group: profileGroup,
+          admin: profileModerator.publicKey,
+        },
+        signers: [profileModerator],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(groupAccount.announcementOnly)
+  })
+
+  const proposalSeed = Buffer.from(anchor.utils.bytes.utf8.encode('proposal'))
+  const voteSeed = Buffer.from(anchor.utils.bytes.utf8.encode('vote'))
+  let proposal: anchor.web3.PublicKey
+
+  const proposalOf = (group: anchor.web3.PublicKey, id: number) => {
+    const idBuffer = Buffer.alloc(4)
+    idBuffer.writeUInt32LE(id)
+    return anchor.utils.publicKey.findProgramAddressSync(
+      [group.toBuffer(), idBuffer, proposalSeed],
+      program.programId,
+    )[0]
+  }
+
+  const voteOf = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [proposal.toBuffer(), invitation(user, profileGroup).toBuffer(), voteSeed],
+      program.programId,
+    )[0]
+
+  const castVote = async (user: anchor.web3.Keypair, approve: boolean) => {
+    await program.rpc.castVote(approve, {
+      accounts: {
+        vote: voteOf(user.publicKey),
+        proposal: proposal,
+        group: profileGroup,
+        invitation: invitation(user.publicKey, profileGroup),
+        signer: user.publicKey,
+        payer: profileAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [user, profileAdmin],
+    })
+  }
+
+  const executeProposal = async () => {
+    await program.rpc.executeProposal({
+      accounts: {
+        proposal: proposal,
+        group: profileGroup,
+      },
+    })
+  }
+
+  it('Member creates a proposal to rename the group', async () => {
+    proposal = proposalOf(profileGroup, 0)
+    const deadline = new anchor.BN(Math.floor(Date.now() / 1000) + 5)
+
+    await program.rpc.createProposal(
+      { rename: { name: 'Governed group' } },
+      deadline,
+      {
+        accounts: {
+          proposal: proposal,
+          group: profileGroup,
+          invitation: invitation(profileUser.publicKey, profileGroup),
+          signer: profileUser.publicKey,
+          payer: profileAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [profileUser, profileAdmin],
+      },
+    )
+
+    let proposalAccount = await program.account.proposal.fetch(proposal)
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(proposalAccount.proposer.equals(profileUser.publicKey))
+    assert.ok(proposalAccount.action.rename.name == 'Governed group')
+    assert.ok(!proposalAccount.executed)
+    assert.ok(groupAccount.proposals == 1)
+  })
+
+  it('Members vote once per invitation', async () => {
+    await castVote(profileUser, true)
+    await castVote(profileModerator, true)
+
+    try {
+      await castVote(profileUser, true)
+      assert.ok(false)
+    } catch (err) {
+      assert.ok(!(err instanceof assert.AssertionError))
+    }
+
+    let proposalAccount = await program.account.proposal.fetch(proposal)
+    assert.ok(proposalAccount.votesFor == 2)
+    assert.ok(proposalAccount.votesAgainst == 0)
+    assert.ok(proposalAccount.openVotes == 2)
+  })
+
+  it('Proposal cannot be executed before the deadline', async () => {
+    try {
+      await executeProposal()
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Voting period is not over'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(groupAccount.name == name)
+  })
+
+  it('Member cannot vote after the deadline', async () => {
+    await new Promise((resolve) => setTimeout(resolve, 7000))
+
+    try {
+      await castVote(profileAdmin, false)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Voting period is over'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Accepted proposal cannot be closed before it is executed', async () => {
+    try {
+      await program.rpc.closeVote({
+        accounts: {
+          vote: voteOf(profileUser.publicKey),
+          proposal: proposal,
+          payer: profileAdmin.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Proposal can still be executed'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    let proposalAccount = await program.account.proposal.fetch(proposal)
+    assert.ok(proposalAccount.openVotes == 2)
+  })
+
+  it('Anyone executes an accepted proposal', async () => {
+    await executeProposal()
+
+    let proposalAccount = await program.account.proposal.fetch(proposal)
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(proposalAccount.executed)
+    assert.ok(groupAccount.name == 'Governed group')
+  })
+
+  it('Proposal cannot be executed or voted on twice', async () => {
+    for (const attempt of [
+      () => executeProposal(),
+      () => castVote(profileAdmin, true),
+    ]) {
+      try {
+        await attempt()
+        assert.ok(false)
+      } catch (err) {
+        const errMsg = 'Proposal was already executed'
+        assert.equal(err.toString(), errMsg)
+      }
+    }
+  })
+
+  it('Proposal closes once all votes are closed', async () => {
+    try {
+      await program.rpc.closeProposal({
+        accounts: {
+          proposal: proposal,
+          payer: profileAdmin.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Proposal still has open votes'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    for (const user of [profileUser, profileModerator]) {
+      await program.rpc.closeVote({
+        accounts: {
+          vote: voteOf(user.publicKey),
+          proposal: proposal,
+          payer: profileAdmin.publicKey,
+        },
+      })
+    }
+    await program.rpc.closeProposal({
+      accounts: {
+        proposal: proposal,
+        payer: profileAdmin.publicKey,
+      },
+    })
+
+    let proposalAccount = await program.account.proposal.fetchNullable(proposal)
+    assert.ok(proposalAccount == null)
+  })
+
+  it('Rejected proposal closes right after the deadline', async () => {
+    proposal = proposalOf(profileGroup, 1)
+    const deadline = new anchor.BN(Math.floor(Date.now() / 1000) + 3)
+    await program.rpc.createProposal({ dissolve: {} }, deadline, {
+      accounts: {
+        proposal: proposal,
+        group: profileGroup,
+        invitation: invitation(profileUser.publicKey, profileGroup),
+        signer: profileUser.publicKey,
+        payer: profileAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [profileUser, profileAdmin],
+    })
+    await castVote(profileModerator, false)
+    await new Promise((resolve) => setTimeout(resolve, 5000))
+
+    await program.rpc.closeVote({
+      accounts: {
+        vote: voteOf(profileModerator.publicKey),
+        proposal: proposal,
+        payer: profileAdmin.publicKey,
+      },
+    })
+    await program.rpc.closeProposal({
+      accounts: {
+        proposal: proposal,
+        payer: profileAdmin.publicKey,
+      },
+    })
+
+    let proposalAccount = await program.account.proposal.fetchNullable(proposal)
+    assert.ok(proposalAccount == null)
+  })
+
+  it('Admin elected by vote still has to be a member to accept', async () => {
+    const outsider = anchor.web3.Keypair.generate()
+    proposal = proposalOf(profileGroup, 2)
+    const deadline = new anchor.BN(Math.floor(Date.now() / 1000) + 3)
+    await program.rpc.createProposal(
+      { changeAdmin: { newAdmin: outsider.publicKey } },
+      deadline,
+      {
+        accounts: {
+          proposal: proposal,
+          group: profileGroup,
+          invitation: invitation(profileUser.publicKey, profileGroup),
+          signer: profileUser.publicKey,
+          payer: profileAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [profileUser, profileAdmin],
+      },
+    )
+    await castVote(profileUser, true)
+    await castVote(profileModerator, true)
+    await new Promise((resolve) => setTimeout(resolve, 5000))
+    await executeProposal()
+
+    let groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(groupAccount.pendingAdmin.equals(outsider.publicKey))
+
+    // the outsider has no invitation to pass, and borrowing a member's fails
+    try {
+      await program.rpc.acceptAdmin({
+        accounts: {
+          group: profileGroup,
+          invitation: invitation(profileUser.publicKey, profileGroup),
+          rosterPage: rosterPage(profileGroup, 0),
+          signer: outsider.publicKey,
+        },
+        signers: [outsider],
+      })
+      assert.ok(false)
+    } catch (err) {
+      assert.ok(!(err instanceof assert.AssertionError))
+    }
+
+    await program.rpc.cancelAdminProposal({
+      accounts: {
+        group: profileGroup,
+        admin: profileAdmin.publicKey,
+      },
+      signers: [profileAdmin],
+    })
+    groupAccount = await program.account.group.fetch(profileGroup)
+    assert.ok(groupAccount.admin.equals(profileAdmin.publicKey))
+  })
+
+  const multisigSeed = Buffer.from(anchor.utils.bytes.utf8.encode('multisig'))
+  const multisigTransactionSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('multisigtx'),
+  )
+  const multisigAdmin = anchor.web3.Keypair.generate()
+  const multisigOwner1 = anchor.web3.Keypair.generate()
+  const multisigOwner2 = anchor.web3.Keypair.generate()
+  let multisigGroup: anchor.web3.PublicKey
+  let multisig: anchor.web3.PublicKey
+  let multisigTransaction: anchor.web3.PublicKey
+  let renameInstruction: anchor.web3.TransactionInstruction
+
+  const executeMultisigTransaction = async () => {
+    await program.rpc.executeMultisigTransaction({
+      accounts: {
+        transaction: multisigTransaction,
+        multisig: multisig,
+      },
+      remainingAccounts: renameInstruction.keys
+        .map((key) => ({ ...key, isSigner: false }))
+        .concat({
+          pubkey: program.programId,
+          isWritable: false,
+          isSigner: false,
+        }),
+    })
+  }
+
+  it('Admin hands the group over to a multisig', async () => {
+    await airdrop(multisigAdmin)
+    multisigGroup = await createGroup(multisigAdmin, 'multisiggroup')
+    multisig = anchor.utils.publicKey.findProgramAddressSync(
+      [multisigGroup.toBuffer(), multisigSeed],
+      program.programId,
+    )[0]
+
+    await program.rpc.createMultisig(
+      [
+        multisigAdmin.publicKey,
+        multisigOwner1.publicKey,
+        multisigOwner2.publicKey,
+      ],
+      2,
+      {
+        accounts: {
+          multisig: multisig,
+          group: multisigGroup,
+          admin: multisigAdmin.publicKey,
+          payer: multisigAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [multisigAdmin],
+      },
+    )
+
+    let groupAccount = await program.account.group.fetch(multisigGroup)
+    let multisigAccount = await program.account.multisig.fetch(multisig)
+    assert.ok(groupAccount.admin.equals(multisig))
+    assert.ok(multisigAccount.owners.length == 3)
+    assert.ok(multisigAccount.threshold == 2)
+  })
+
+  it('Former admin cannot modify the group alone', async () => {
+    try {
+      await program.rpc.modifyName('Multisig group', {
+        accounts: {
+          group: multisigGroup,
+          admin: multisigAdmin.publicKey,
+        },
+        signers: [multisigAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Owner proposes a multisig transaction', async () => {
+    multisigTransaction = anchor.utils.publicKey.findProgramAddressSync(
+      [multisig.toBuffer(), Buffer.alloc(4), multisigTransactionSeed],
+      program.programId,
+    )[0]
+    renameInstruction = program.instruction.modifyName('Multisig group', {
+      accounts: {
+        group: multisigGroup,
+        admin: multisig,
+      },
+    })
+
+    await program.rpc.createMultisigTransaction(
+      renameInstruction.keys,
+      renameInstruction.data,
+      {
+        accounts: {
+          transaction: multisigTransaction,
+          multisig: multisig,
+          signer: multisigOwner1.publicKey,
+          payer: multisigAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [multisigOwner1, multisigAdmin],
+      },
+    )
+
+    let transactionAccount = await program.account.multisigTransaction.fetch(
+      multisigTransaction,
+    )
+    assert.ok(transactionAccount.approvals[1])
+    assert.ok(!transactionAccount.approvals[2])
+    assert.ok(!transactionAccount.executed)
+  })
+
+  it('Multisig transaction cannot run below the threshold', async () => {
+    try {
+      await executeMultisigTransaction()
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Transaction does not have enough approvals'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Stranger cannot approve a multisig transaction', async () => {
+    try {
+      await program.rpc.approveMultisigTransaction({
+        accounts: {
+          transaction: multisigTransaction,
+          multisig: multisig,
+          signer: cranker.publicKey,
+        },
+        signers: [cranker],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Multisig transaction runs once the threshold is reached', async () => {
+    await program.rpc.approveMultisigTransaction({
+      accounts: {
+        transaction: multisigTransaction,
+        multisig: multisig,
+        signer: multisigOwner2.publicKey,
+      },
+      signers: [multisigOwner2],
+    })
+    await executeMultisigTransaction()
+
+    let groupAccount = await program.account.group.fetch(multisigGroup)
+    let transactionAccount = await program.account.multisigTransaction.fetch(
+      multisigTransaction,
+    )
+    assert.ok(groupAccount.name == 'Multisig group')
+    assert.ok(transactionAccount.executed)
+  })
+
+  it('Executed multisig transaction is closed', async () => {
+    await program.rpc.closeMultisigTransaction({
+      accounts: {
+        transaction: multisigTransaction,
+        multisig: multisig,
+        payer: multisigAdmin.publicKey,
+      },
+    })
+
+    let transactionAccount =
+      await program.account.multisigTransaction.fetchNullable(
+        multisigTransaction,
+      )
+    assert.ok(transactionAccount == null)
+  })
+
+  it('Multisig cannot list the same owner twice', async () => {
+    const duplicateGroup = await createGroup(multisigAdmin, 'duplicateowners')
+    try {
+      await program.rpc.createMultisig(
+        [multisigOwner1.publicKey, multisigOwner1.publicKey],
+        2,
+        {
+          accounts: {
+            multisig: anchor.utils.publicKey.findProgramAddressSync(
+              [duplicateGroup.toBuffer(), multisigSeed],
+              program.programId,
+            )[0],
+            group: duplicateGroup,
+            admin: multisigAdmin.publicKey,
+            payer: multisigAdmin.publicKey,
+            systemProgram: SystemProgram.programId,
+          },
+          signers: [multisigAdmin],
+        },
+      )
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Multisig owners must be unique'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  const proposeMultisigTransaction = async (
+    id: number,
+    instruction: anchor.web3.TransactionInstruction,
+  ) => {
+    const idBuffer = Buffer.alloc(4)
+    idBuffer.writeUInt32LE(id)
+    const transaction = anchor.utils.publicKey.findProgramAddressSync(
+      [multisig.toBuffer(), idBuffer, multisigTransactionSeed],
+      program.programId,
+    )[0]
+    await program.rpc.createMultisigTransaction(
+      instruction.keys,
+      instruction.data,
+      {
+        accounts: {
+          transaction,
+          multisig: multisig,
+          signer: multisigOwner1.publicKey,
+          payer: multisigAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [multisigOwner1, multisigAdmin],
+      },
+    )
+    await program.rpc.approveMultisigTransaction({
+      accounts: {
+        transaction,
+        multisig: multisig,
+        signer: multisigOwner2.publicKey,
+      },
+      signers: [multisigOwner2],
+    })
+    return transaction
+  }
+
+  const runMultisigTransaction = async (
+    transaction: anchor.web3.PublicKey,
+    instruction: anchor.web3.TransactionInstruction,
+  ) => {
+    await program.rpc.executeMultisigTransaction({
+      accounts: {
+        transaction,
+        multisig: multisig,
+      },
+      remainingAccounts: instruction.keys
+        .map((key) => ({ ...key, isSigner: false }))
+        .concat({
+          pubkey: program.programId,
+          isWritable: false,
+          isSigner: false,
+        }),
+    })
+  }
+
+  it('Changing the owners voids approvals made before the change', async () => {
+    const staleRename = program.instruction.modifyName('Stale name', {
+      accounts: {
+        group: multisigGroup,
+        admin: multisig,
+      },
+    })
+    const staleTransaction = await proposeMultisigTransaction(1, staleRename)
+
+    const ownersChange = program.instruction.modifyMultisig(
+      [multisigOwner1.publicKey, multisigOwner2.publicKey],
+      2,
+      {
+        accounts: {
+          multisig: multisig,
+        },
+      },
+    )
+    await runMultisigTransaction(
+      await proposeMultisigTransaction(2, ownersChange),
+      ownersChange,
+    )
+
+    let multisigAccount = await program.account.multisig.fetch(multisig)
+    assert.ok(multisigAccount.owners.length == 2)
+    assert.ok(multisigAccount.ownersVersion == 1)
+
+    for (const attempt of [
+      () =>
+        program.rpc.approveMultisigTransaction({
+          accounts: {
+            transaction: staleTransaction,
+            multisig: multisig,
+            signer: multisigOwner1.publicKey,
+          },
+          signers: [multisigOwner1],
+        }),
+      () => runMultisigTransaction(staleTransaction, staleRename),
+    ]) {
+      try {
+        await attempt()
+        assert.ok(false)
+      } catch (err) {
+        const errMsg = 'Multisig owners changed since the transaction was created'
+        assert.equal(err.toString(), errMsg)
+      }
+    }
+
+    let groupAccount = await program.account.group.fetch(multisigGroup)
+    assert.ok(groupAccount.name == 'Multisig group')
+  })
+
+  const sponsoredUser = anchor.web3.Keypair.generate()
+  const relayer = anchor.web3.Keypair.generate()
+  const rotatedRelayer = anchor.web3.Keypair.generate()
+  let sponsoredGroup: anchor.web3.PublicKey
+
+  it('Relayer sponsors a group created by another user', async () => {
+    await airdrop(relayer)
+    sponsoredGroup = await createGroup(sponsoredUser, 'sponsored', relayer)
+
+    let groupAccount = await program.account.group.fetch(sponsoredGroup)
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(sponsoredUser.publicKey, sponsoredGroup),
+    )
+    assert.ok(groupAccount.creator.equals(sponsoredUser.publicKey))
+    assert.ok(groupAccount.admin.equals(sponsoredUser.publicKey))
+    assert.ok(groupAccount.payer.equals(relayer.publicKey))
+    assert.ok(invitationAccount.sender.equals(sponsoredUser.publicKey))
+    assert.ok(invitationAccount.payer.equals(relayer.publicKey))
+  })
+
+  it('Group payer cannot be transferred without the admin', async () => {
+    try {
+      await program.rpc.transferPayer({
+        accounts: {
+          group: sponsoredGroup,
+          admin: relayer.publicKey,
+          payer: relayer.publicKey,
+          newPayer: rotatedRelayer.publicKey,
+        },
+        signers: [relayer],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Only the current payer can transfer the group payer', async () => {
+    try {
+      await program.rpc.transferPayer({
+        accounts: {
+          group: sponsoredGroup,
+          admin: sponsoredUser.publicKey,
+          payer: sponsoredUser.publicKey,
+          newPayer: sponsoredUser.publicKey,
+        },
+        signers: [sponsoredUser],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account was not created by provided user'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Relayer rotates its wallet for the group and the invitation', async () => {
+    await program.rpc.transferPayer({
+      accounts: {
+        group: sponsoredGroup,
+        admin: sponsoredUser.publicKey,
+        payer: relayer.publicKey,
+        newPayer: rotatedRelayer.publicKey,
+      },
+      signers: [sponsoredUser, relayer],
+    })
+    await program.rpc.transferInvitationPayer({
+      accounts: {
+        invitation: invitation(sponsoredUser.publicKey, sponsoredGroup),
+        payer: relayer.publicKey,
+        newPayer: rotatedRelayer.publicKey,
+      },
+      signers: [relayer],
+    })
+
+    let groupAccount = await program.account.group.fetch(sponsoredGroup)
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(sponsoredUser.publicKey, sponsoredGroup),
+    )
+    assert.ok(groupAccount.payer.equals(rotatedRelayer.publicKey))
+    assert.ok(invitationAccount.payer.equals(rotatedRelayer.publicKey))
+  })
+
+  it('Only the current payer can transfer the invitation payer', async () => {
+    try {
+      await program.rpc.transferInvitationPayer({
+        accounts: {
+          invitation: invitation(sponsoredUser.publicKey, sponsoredGroup),
+          payer: relayer.publicKey,
+          newPayer: relayer.publicKey,
+        },
+        signers: [relayer],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account was not created by provided user'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Closing a sponsored group refunds the rotated relayer', async () => {
+    const closeAccounts = (payer: anchor.web3.PublicKey) => ({
+      group: sponsoredGroup,
+      invitation: invitation(sponsoredUser.publicKey, sponsoredGroup),
+      membershipIndex: membershipIndex(sponsoredUser.publicKey),
+      membershipPage: membershipPage(sponsoredUser.publicKey, 0),
+      membershipLastPage: membershipPage(sponsoredUser.publicKey, 0),
+      rosterPage: rosterPage(sponsoredGroup, 0),
+      signer: sponsoredUser.publicKey,
+      groupPayer: payer,
+      invitationPayer: payer,
+      // the roster page keeps refunding the relayer that created it
+      rosterPagePayer: relayer.publicKey,
+    })
+
+    try {
+      await program.rpc.close({
+        accounts: closeAccounts(relayer.publicKey),
+        signers: [sponsoredUser],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account was not created by provided user'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    const balanceBefore = await provider.connection.getBalance(
+      rotatedRelayer.publicKey,
+    )
+    await program.rpc.close({
+      accounts: closeAccounts(rotatedRelayer.publicKey),
+      signers: [sponsoredUser],
+    })
+    const balanceAfter = await provider.connection.getBalance(
+      rotatedRelayer.publicKey,
+    )
+
+    let groupAccount = await program.account.group.fetchNullable(sponsoredGroup)
+    assert.ok(groupAccount == null)
+    assert.ok(balanceAfter > balanceBefore)
+  })
+
+  const deactivatedAdmin = anchor.web3.Keypair.generate()
+  const deactivatedUser = anchor.web3.Keypair.generate()
+  const userSeed = Buffer.from(anchor.utils.bytes.utf8.encode('user'))
+  let deactivatedGroup: anchor.web3.PublicKey
+
+  const userProfile = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), userSeed],
+      usersProgram.programId,
+    )[0]
+  const closedProfile = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), Buffer.from('closedprofile')],
+      usersProgram.programId,
+    )[0]
+
+  const createAndDeactivateProfile = async (user: anchor.web3.Keypair) => {
+    await usersProgram.rpc.create('Leaving', '', '', {
+      accounts: {
+        user: userProfile(user.publicKey),
+        signer: user.publicKey,
+        payer: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [user],
+    })
+    await usersProgram.rpc.deactivate({
+      accounts: {
+        user: userProfile(user.publicKey),
+        signer: user.publicKey,
+      },
+      signers: [user],
+    })
+  }
+
+  const removeDeactivatedMember = async (user: anchor.web3.PublicKey) => {
+    await program.rpc.removeDeactivatedMember({
+      accounts: {
+        group: deactivatedGroup,
+        invitation: invitation(user, deactivatedGroup),
+        user: userProfile(user),
+        closedProfile: closedProfile(user),
+        membershipIndex: membershipIndex(user),
+        membershipPage: membershipPage(user, 0),
+        membershipLastPage: membershipPage(user, 0),
+        rosterPage: rosterPage(deactivatedGroup, 0),
+        rosterLastPage: rosterPage(deactivatedGroup, 0),
+        invitationPayer: deactivatedAdmin.publicKey,
+      },
+    })
+  }
+
+  it('Active members cannot be removed by others', async () => {
+    await airdrop(deactivatedAdmin)
+    await airdrop(deactivatedUser)
+    deactivatedGroup = await createGroup(deactivatedAdmin, 'deactivatedgroup')
+    await invite(deactivatedGroup, deactivatedAdmin, deactivatedUser.publicKey)
+    await usersProgram.rpc.create('Leaving', '', '', {
+      accounts: {
+        user: userProfile(deactivatedUser.publicKey),
+        signer: deactivatedUser.publicKey,
+        payer: deactivatedUser.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [deactivatedUser],
+    })
+
+    try {
+      await removeDeactivatedMember(deactivatedUser.publicKey)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User profile is not deactivated'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Profile of another user does not remove a member', async () => {
+    await createAndDeactivateProfile(deactivatedAdmin)
+
+    try {
+      await program.rpc.removeDeactivatedMember({
+        accounts: {
+          group: deactivatedGroup,
+          invitation: invitation(deactivatedUser.publicKey, deactivatedGroup),
+          user: userProfile(deactivatedAdmin.publicKey),
+          closedProfile: closedProfile(deactivatedAdmin.publicKey),
+          membershipIndex: membershipIndex(deactivatedUser.publicKey),
+          membershipPage: membershipPage(deactivatedUser.publicKey, 0),
+          membershipLastPage: membershipPage(deactivatedUser.publicKey, 0),
+          rosterPage: rosterPage(deactivatedGroup, 0),
+          rosterLastPage: rosterPage(deactivatedGroup, 0),
+          invitationPayer: deactivatedAdmin.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User profile is not deactivated'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Deactivated members are removed permissionlessly', async () => {
+    await usersProgram.rpc.deactivate({
+      accounts: {
+        user: userProfile(deactivatedUser.publicKey),
+        signer: deactivatedUser.publicKey,
+      },
+      signers: [deactivatedUser],
+    })
+    await removeDeactivatedMember(deactivatedUser.publicKey)
+
+    let groupAccount = await program.account.group.fetch(deactivatedGroup)
+    let invitationAccount = await program.account.invitation.fetchNullable(
+      invitation(deactivatedUser.publicKey, deactivatedGroup),
+    )
+    let rosterAccount = await program.account.rosterPage.fetch(
+      rosterPage(deactivatedGroup, 0),
+    )
+    assert.ok(groupAccount.members == 1)
+    assert.ok(invitationAccount == null)
+    assert.ok(
+      !rosterAccount.members.some((member) =>
+        member.equals(deactivatedUser.publicKey),
+      ),
+    )
+  })
+
+  const closeProfile = async (user: anchor.web3.Keypair) => {
+    await usersProgram.rpc.close({
+      accounts: {
+        user: userProfile(user.publicKey),
+        signer: user.publicKey,
+        payer: user.publicKey,
+        friendIndex: friendIndex(user.publicKey),
+        membershipIndex: membershipIndex(user.publicKey),
+        closedProfile: closedProfile(user.publicKey),
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [user],
+    })
+  }
+
+  it('Deactivated admin cannot close the profile while in a group', async () => {
+    try {
+      await closeProfile(deactivatedAdmin)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Friends and groups have to be cleaned up first'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Removing a deactivated admin dissolves the group', async () => {
+    await removeDeactivatedMember(deactivatedAdmin.publicKey)
+
+    let groupAccount = await program.account.group.fetch(deactivatedGroup)
+    let indexAccount = await program.account.membershipIndex.fetch(
+      membershipIndex(deactivatedAdmin.publicKey),
+    )
+    assert.ok(groupAccount.dissolved)
+    assert.ok(groupAccount.members == 0)
+    assert.ok(indexAccount.groups == 0)
+
+    await closeProfile(deactivatedAdmin)
+    let profileAccount = await usersProgram.account.user.fetchNullable(
+      userProfile(deactivatedAdmin.publicKey),
+    )
+    assert.ok(profileAccount == null)
+  })
+
+  const friendsAdmin = anchor.web3.Keypair.generate()
+  const friendOfAdmin = anchor.web3.Keypair.generate()
+  const strangerToAdmin = anchor.web3.Keypair.generate()
+  let friendsGroup: anchor.web3.PublicKey
+
+  const friendSeed = (seed: string) =>
+    Buffer.from(anchor.utils.bytes.utf8.encode(seed))
+
+  const friendRequest = (
+    user: anchor.web3.PublicKey,
+    friend: anchor.web3.PublicKey,
+  ) => {
+    const [user1, user2] =
+      Buffer.compare(user.toBuffer(), friend.toBuffer()) > 0
+        ? [user, friend]
+        : [friend, user]
+    return anchor.utils.publicKey.findProgramAddressSync(
+      [user1.toBuffer(), user2.toBuffer()],
+      friendsProgram.programId,
+    )[0]
+  }
+
+  const friendIndex = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), friendSeed('friendindex')],
+      friendsProgram.programId,
+    )[0]
+
+  const friendPage = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), Buffer.alloc(4), friendSeed('friendpage')],
+      friendsProgram.programId,
+    )[0]
+
+  const requestAccount = (request: anchor.web3.PublicKey) => [
+    { pubkey: request, isWritable: false, isSigner: false },
+  ]
+
+  it('Admin restricts invites to friends', async () => {
+    await airdrop(friendsAdmin)
+    await airdrop(friendOfAdmin)
+    friendsGroup = await createGroup(friendsAdmin, 'friendsgroup')
+
+    await program.rpc.modifyInviteOnlyFriends(true, {
+      accounts: {
+        group: friendsGroup,
+        admin: friendsAdmin.publicKey,
+      },
+      signers: [friendsAdmin],
+    })
+
+    let groupAccount = await program.account.group.fetch(friendsGroup)
+    assert.ok(groupAccount.inviteOnlyFriends)
+  })
+
+  it('Cannot invite a user who is not a friend', async () => {
+    try {
+      await invite(friendsGroup, friendsAdmin, strangerToAdmin.publicKey)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Inviter and recipient are not friends'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Cannot invite a friend with a pending request', async () => {
+    const [user1, user2] =
+      Buffer.compare(
+        friendsAdmin.publicKey.toBuffer(),
+        friendOfAdmin.publicKey.toBuffer(),
+      ) > 0
+        ? [friendsAdmin.publicKey, friendOfAdmin.publicKey]
+        : [friendOfAdmin.publicKey, friendsAdmin.publicKey]
+    await friendsProgram.rpc.makeRequest(user1, user2, encryptionKey, {
+      accounts: {
+        request: friendRequest(friendsAdmin.publicKey, friendOfAdmin.publicKey),
+        user: friendsAdmin.publicKey,
+        payer: friendsAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [friendsAdmin],
+    })
+
+    try {
+      await invite(
+        friendsGroup,
+        friendsAdmin,
+        friendOfAdmin.publicKey,
+        requestAccount(
+          friendRequest(friendsAdmin.publicKey, friendOfAdmin.publicKey),
+        ),
+      )
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Inviter and recipient are not friends'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Admin invites a friend', async () => {
+    const request = friendRequest(
+      friendsAdmin.publicKey,
+      friendOfAdmin.publicKey,
+    )
+    await friendsProgram.rpc.acceptRequest(encryptionKey, {
+      accounts: {
+        request,
+        indexed: anchor.utils.publicKey.findProgramAddressSync(
+          [request.toBuffer(), friendSeed('indexed')],
+          friendsProgram.programId,
+        )[0],
+        fromIndex: friendIndex(friendsAdmin.publicKey),
+        fromPage: friendPage(friendsAdmin.publicKey),
+        toIndex: friendIndex(friendOfAdmin.publicKey),
+        toPage: friendPage(friendOfAdmin.publicKey),
+        user: friendOfAdmin.publicKey,
+        payer: friendOfAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [friendOfAdmin],
+    })
+
+    try {
+      await invite(
+        friendsGroup,
+        friendsAdmin,
+        strangerToAdmin.publicKey,
+        requestAccount(request),
+      )
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Inviter and recipient are not friends'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    await invite(
+      friendsGroup,
+      friendsAdmin,
+      friendOfAdmin.publicKey,
+      requestAccount(request),
+    )
+
+    let groupAccount = await program.account.group.fetch(friendsGroup)
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(friendOfAdmin.publicKey, friendsGroup),
+    )
+    assert.ok(groupAccount.members == 2)
+    assert.ok(invitationAccount.recipient.equals(friendOfAdmin.publicKey))
+  })
+
+  it('Cannot invite a friend once the friendship is removed', async () => {
+    const request = friendRequest(
+      friendsAdmin.publicKey,
+      friendOfAdmin.publicKey,
+    )
+    await friendsProgram.rpc.removeFriend({
+      accounts: {
+        request,
+        fromIndex: friendIndex(friendsAdmin.publicKey),
+        fromPage: friendPage(friendsAdmin.publicKey),
+        fromLastPage: friendPage(friendsAdmin.publicKey),
+        toIndex: friendIndex(friendOfAdmin.publicKey),
+        toPage: friendPage(friendOfAdmin.publicKey),
+        toLastPage: friendPage(friendOfAdmin.publicKey),
+        user: friendsAdmin.publicKey,
+        indexed: anchor.utils.publicKey.findProgramAddressSync(
+          [request.toBuffer(), friendSeed('indexed')],
+          friendsProgram.programId,
+        )[0],
+        indexedPayer: friendOfAdmin.publicKey,
+      },
+      signers: [friendsAdmin],
+    })
+    const otherFriendsGroup = await createGroup(friendsAdmin, 'friendsgroup2')
+    await program.rpc.modifyInviteOnlyFriends(true, {
+      accounts: {
+        group: otherFriendsGroup,
+        admin: friendsAdmin.publicKey,
+      },
+      signers: [friendsAdmin],
+    })
+
+    try {
+      await invite(
+        otherFriendsGroup,
+        friendsAdmin,
+        friendOfAdmin.publicKey,
+        requestAccount(request),
+      )
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Inviter and recipient are not friends'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  // legacy group preloaded from tests/fixtures, see tests/fixtures/generate.js
+  const legacyAdmin = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 3))
+  const legacyMember = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 4))
+  const legacyGroup = anchor.utils.publicKey.findProgramAddressSync(
+    [
+      createHash('sha256')
+        .update('legacygroup')
+        .update(legacyAdmin.publicKey.toBuffer())
+        .digest(),
+      groupSeed,
+    ],
+    program.programId,
+  )[0]
+
+  // rent freed by shrinking a legacy invitation to the current layout
+  const migrationRefund = async (migrated: anchor.web3.PublicKey) => {
+    const legacyRent =
+      await provider.connection.getMinimumBalanceForRentExemption(337)
+    const migratedInfo = await provider.connection.getAccountInfo(migrated)
+    assert.equal(
+      migratedInfo.lamports,
+      await provider.connection.getMinimumBalanceForRentExemption(
+        migratedInfo.data.length,
+      ),
+    )
+    return legacyRent - migratedInfo.lamports
+  }
+
+  const migrateLegacyInvitation = async (recipient: anchor.web3.PublicKey) => {
+    await program.rpc.migrateInvitation(recipient, {
+      accounts: {
+        group: legacyGroup,
+        invitation: invitation(recipient, legacyGroup),
+        membershipIndex: membershipIndex(recipient),
+        membershipPage: membershipPage(recipient, 0),
+        rosterPage: rosterPage(legacyGroup, 0),
+        payer: provider.wallet.publicKey,
+        invitationPayer: legacyAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+    })
+  }
+
+  it('Admin migrates a legacy group', async () => {
+    await program.rpc.migrateGroup({
+      accounts: {
+        group: legacyGroup,
+        invitation: invitation(legacyAdmin.publicKey, legacyGroup),
+        membershipIndex: membershipIndex(legacyAdmin.publicKey),
+        membershipPage: membershipPage(legacyAdmin.publicKey, 0),
+        rosterPage: rosterPage(legacyGroup, 0),
+        signer: legacyAdmin.publicKey,
+        payer: provider.wallet.publicKey,
+        invitationPayer: legacyAdmin.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [legacyAdmin],
+    })
+
+    let groupAccount = await program.account.group.fetch(legacyGroup)
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(legacyAdmin.publicKey, legacyGroup),
+    )
+    let roster = await program.account.rosterPage.fetch(
+      rosterPage(legacyGroup, 0),
+    )
+    let index = await program.account.membershipIndex.fetch(
+      membershipIndex(legacyAdmin.publicKey),
+    )
+    assert.ok(groupAccount.members == 1)
+    assert.equal(groupAccount.name, 'Legacy group')
+    assert.equal(groupAccount.groupId, 'legacygroup')
+    assert.ok(groupAccount.payer.equals(legacyAdmin.publicKey))
+    assert.ok(invitationAccount.payer.equals(legacyAdmin.publicKey))
+    assert.ok(Object.keys(invitationAccount.role)[0] == 'member')
+    assert.equal(roster.members.length, 1)
+    assert.ok(roster.members[0].equals(legacyAdmin.publicKey))
+    assert.ok(index.groups == 1)
+    assert.equal(
+      await provider.connection.getBalance(legacyAdmin.publicKey),
+      await migrationRefund(invitation(legacyAdmin.publicKey, legacyGroup)),
+    )
+  })
+
+  it('Migrated group cannot be migrated again', async () => {
+    try {
+      await program.rpc.migrateGroup({
+        accounts: {
+          group: legacyGroup,
+          invitation: invitation(legacyMember.publicKey, legacyGroup),
+          membershipIndex: membershipIndex(legacyAdmin.publicKey),
+          membershipPage: membershipPage(legacyAdmin.publicKey, 0),
+          rosterPage: rosterPage(legacyGroup, 0),
+          signer: legacyAdmin.publicKey,
+          payer: provider.wallet.publicKey,
+          invitationPayer: legacyAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [legacyAdmin],
+      })
+      assert.ok(false)
+    } catch (err) {
+      assert.ok(err.logs.some((log) => log.includes('already in use')))
+    }
+  })
+
+  it('Legacy invitation is only migrated for its recipient', async () => {
+    try {
+      await program.rpc.migrateInvitation(legacyAdmin.publicKey, {
+        accounts: {
+          group: legacyGroup,
+          invitation: invitation(legacyMember.publicKey, legacyGroup),
+          membershipIndex: membershipIndex(legacyAdmin.publicKey),
+          membershipPage: membershipPage(legacyAdmin.publicKey, 0),
+          rosterPage: rosterPage(legacyGroup, 0),
+          payer: provider.wallet.publicKey,
+          invitationPayer: legacyAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Invite does not match Group ID'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Legacy invitation is migrated into the roster', async () => {
+    const adminBalance = await provider.connection.getBalance(
+      legacyAdmin.publicKey,
+    )
+    await migrateLegacyInvitation(legacyMember.publicKey)
+
+    let groupAccount = await program.account.group.fetch(legacyGroup)
+    let invitationAccount = await program.account.invitation.fetch(
+      invitation(legacyMember.publicKey, legacyGroup),
+    )
+    let roster = await program.account.rosterPage.fetch(
+      rosterPage(legacyGroup, 0),
+    )
+    let page = await program.account.membershipPage.fetch(
+      membershipPage(legacyMember.publicKey, 0),
+    )
+    assert.ok(groupAccount.members == 2)
+    assert.ok(invitationAccount.recipient.equals(legacyMember.publicKey))
+    assert.ok(invitationAccount.sender.equals(legacyAdmin.publicKey))
+    assert.equal(roster.members.length, 2)
+    assert.ok(roster.members[1].equals(legacyMember.publicKey))
+    assert.equal(page.groups.length, 1)
+    assert.ok(page.groups[0].equals(legacyGroup))
+    assert.equal(
+      (await provider.connection.getBalance(legacyAdmin.publicKey)) -
+        adminBalance,
+      await migrationRefund(invitation(legacyMember.publicKey, legacyGroup)),
+    )
+  })
+
+  it('Migrated invitation cannot be migrated again', async () => {
+    try {
+      await migrateLegacyInvitation(legacyMember.publicKey)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account is not in the legacy layout'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  // fixtures never migrated, see tests/fixtures/generate.js
+  const staleMember = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 6))
+  const orphanMember = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 5))
+  const closedLegacyGroup = anchor.utils.publicKey.findProgramAddressSync(
+    [
+      createHash('sha256')
+        .update('legacyclosedgroup')
+        .update(legacyAdmin.publicKey.toBuffer())
+        .digest(),
+      groupSeed,
+    ],
+    program.programId,
+  )[0]
+
+  const reapLegacyInvitation = async (
+    recipient: anchor.web3.PublicKey,
+    group: anchor.web3.PublicKey,
+    claimReward: boolean,
+  ) => {
+    await program.rpc.reapLegacyInvitation(claimReward, {
+      accounts: {
+        invitation: invitation(recipient, group),
+        group,
+        invitationPayer: legacyAdmin.publicKey,
+        cranker: cranker.publicKey,
+      },
+      signers: [cranker],
+    })
+  }
+
+  it('Legacy invitation of a live group cannot be reaped', async () => {
+    try {
+      await reapLegacyInvitation(staleMember.publicKey, legacyGroup, true)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Group is still active'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Legacy invitation of a dissolved group is reaped', async () => {
+    await program.rpc.dissolve({
+      accounts: {
+        group: legacyGroup,
+        admin: legacyAdmin.publicKey,
+      },
+      signers: [legacyAdmin],
+    })
+    const stale = invitation(staleMember.publicKey, legacyGroup)
+    const rent = (await provider.connection.getAccountInfo(stale)).lamports
+    const crankerBalance = await provider.connection.getBalance(
+      cranker.publicKey,
+    )
+    const payerBalance = await provider.connection.getBalance(
+      legacyAdmin.publicKey,
+    )
+
+    await reapLegacyInvitation(staleMember.publicKey, legacyGroup, true)
+
+    assert.equal(await provider.connection.getAccountInfo(stale), null)
+    assert.equal(
+      await provider.connection.getBalance(cranker.publicKey),
+      crankerBalance + 5000,
+    )
+    assert.equal(
+      await provider.connection.getBalance(legacyAdmin.publicKey),
+      payerBalance + rent - 5000,
+    )
+    let groupAccount = await program.account.group.fetch(legacyGroup)
+    assert.ok(groupAccount.members == 2)
+  })
+
+  it('Legacy invitation of a closed group is reaped', async () => {
+    const orphan = invitation(orphanMember.publicKey, closedLegacyGroup)
+    const rent = (await provider.connection.getAccountInfo(orphan)).lamports
+    const payerBalance = await provider.connection.getBalance(
+      legacyAdmin.publicKey,
+    )
+    assert.equal(
+      await provider.connection.getAccountInfo(closedLegacyGroup),
+      null,
+    )
+
+    await reapLegacyInvitation(orphanMember.publicKey, closedLegacyGroup, false)
+
+    assert.equal(await provider.connection.getAccountInfo(orphan), null)
+    assert.equal(
+      await provider.connection.getBalance(legacyAdmin.publicKey),
+      payerBalance + rent,
+    )
+  })
+
+  it('Current invitation is not reaped as a legacy one', async () => {
+    try {
+      await reapLegacyInvitation(legacyMember.publicKey, legacyGroup, false)
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account is not in the legacy layout'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Private group is created without directory accounts', async () => {
+    const privateAdmin = anchor.web3.Keypair.generate()
+    const privateCategory = 7
+    const hash = createHash('sha256')
+      .update('privategroup')
+      .update(privateAdmin.publicKey.toBuffer())
+      .digest()
+    const privateGroup = anchor.utils.publicKey.findProgramAddressSync(
+      [hash, groupSeed],
+      program.programId,
+    )[0]
+    await airdrop(privateAdmin)
+
+    await program.rpc.create(
+      hash,
+      'privategroup',
+      true,
+      name,
+      encryptionKey,
+      dbType,
+      { private: {} },
+      privateCategory,
+      '',
+      [],
+      {
+        accounts: {
+          group: privateGroup,
+          invitation: invitation(privateAdmin.publicKey, privateGroup),
+          membershipIndex: membershipIndex(privateAdmin.publicKey),
+          membershipPage: membershipPage(privateAdmin.publicKey, 0),
+          rosterPage: rosterPage(privateGroup, 0),
+          signer: privateAdmin.publicKey,
+          payer: privateAdmin.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [privateAdmin],
+      },
+    )
+
+    let groupAccount = await program.account.group.fetch(privateGroup)
+    assert.ok(groupAccount.category == privateCategory)
+    assert.equal(
+      await provider.connection.getAccountInfo(directory(privateCategory)),
+      null,
+    )
+    assert.equal(
+      await provider.connection.getAccountInfo(
+        directoryPage(privateCategory, 0),
+      ),
+      null,
+    )
+  })
+
+  it('Closing a dissolved group closes every roster page', async () => {
+    const rosterAdmin = anchor.web3.Keypair.generate()
+    await airdrop(rosterAdmin)
+    const rosterGroup = await createGroup(rosterAdmin, 'rostergroup')
+    const members = Array.from({ length: 32 }, () =>
+      anchor.web3.Keypair.generate().publicKey,
+    )
+    for (const [i, member] of members.entries()) {
+      // the 33rd member is the first one on the second roster page
+      await invite(rosterGroup, rosterAdmin, member, [], i == 31 ? 1 : 0)
+    }
+
+    let groupAccount = await program.account.group.fetch(rosterGroup)
+    assert.ok(groupAccount.members == 33)
+    assert.ok(groupAccount.rosterPages == 2)
+
+    await program.rpc.dissolve({
+      accounts: {
+        group: rosterGroup,
+        admin: rosterAdmin.publicKey,
+      },
+      signers: [rosterAdmin],
+    })
+    const leavers = [...members.reverse(), rosterAdmin.publicKey]
+    for (const [i, member] of leavers.entries()) {
+      const page = i == 0 ? 1 : 0
+      await program.rpc.leave({
+        accounts: {
+          group: rosterGroup,
+          invitation: invitation(member, rosterGroup),
+          membershipIndex: membershipIndex(member),
+          membershipPage: membershipPage(member, 0),
+          membershipLastPage: membershipPage(member, 0),
+          rosterPage: rosterPage(rosterGroup, page),
+          rosterLastPage: rosterPage(rosterGroup, page),
+          signer: rosterAdmin.publicKey,
+          invitationPayer: rosterAdmin.publicKey,
+        },
+        signers: [rosterAdmin],
+      })
+    }
+
+    const closeAccounts = {
+      group: rosterGroup,
+      rosterPage: rosterPage(rosterGroup, 0),
+      groupPayer: rosterAdmin.publicKey,
+      rosterPagePayer: rosterAdmin.publicKey,
+    }
+    try {
+      await program.rpc.closeDissolved({ accounts: closeAccounts })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Index does not match the provided accounts'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    await program.rpc.closeDissolved({
+      accounts: closeAccounts,
+      remainingAccounts: [
+        {
+          pubkey: rosterPage(rosterGroup, 1),
+          isSigner: false,
+          isWritable: true,
+        },
+        {
+          pubkey: rosterAdmin.publicKey,
+          isSigner: false,
+          isWritable: true,
+        },
+      ],
+    })
+
+    for (const account of [
+      rosterGroup,
+      rosterPage(rosterGroup, 0),
+      rosterPage(rosterGroup, 1),
+    ]) {
+      assert.equal(await provider.connection.getAccountInfo(account), null)
+    }
+
+    // the membership accounts of the invited members were paid by the admin
+    const member = members[0]
+    try {
+      await program.rpc.closeMembershipIndex({
+        accounts: {
+          membershipIndex: membershipIndex(rosterAdmin.publicKey),
+          payer: rosterAdmin.publicKey,
+        },
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account still has entries'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    await program.rpc.closeMembershipPage({
+      accounts: {
+        membershipPage: membershipPage(member, 0),
+        payer: rosterAdmin.publicKey,
+      },
+    })
+    await program.rpc.closeMembershipIndex({
+      accounts: {
+        membershipIndex: membershipIndex(member),
+        payer: rosterAdmin.publicKey,
+      },
+    })
+    for (const account of [membershipIndex(member), membershipPage(member, 0)]) {
+      assert.equal(await provider.connection.getAccountInfo(account), null)
+    }
+  })
 })
@@ -0,0 +1,497 @@
+import * as anchor from '@project-serum/anchor'
+import { Program } from '@project-serum/anchor'
+import { Sponsorship } from '../target/types/sponsorship'
+import { Users } from '../target/types/users'
+import { Friends } from '../target/types/friends'
+import { Groupchats } from '../target/types/groupchats'
+import assert from 'assert'
+import { createHash } from 'crypto'
+const { SystemProgram, SYSVAR_INSTRUCTIONS_PUBKEY } = anchor.web3
+
+describe('sponsorship', () => {
+  const provider = anchor.Provider.env()
+
+  // Configure the client to use the local cluster.
+  anchor.setProvider(provider)
+
+  // Programs for the tests.
+  const program = anchor.workspace.Sponsorship as Program<Sponsorship>
+  const usersProgram = anchor.workspace.Users as Program<Users>
+  const friendsProgram = anchor.workspace.Friends as Program<Friends>
+  const groupchatsProgram = anchor.workspace.Groupchats as Program<Groupchats>
+
+  const sponsorSeed = Buffer.from(anchor.utils.bytes.utf8.encode('sponsor'))
+  const vaultSeed = Buffer.from(anchor.utils.bytes.utf8.encode('vault'))
+  const quotaSeed = Buffer.from(anchor.utils.bytes.utf8.encode('quota'))
+  const userSeed = Buffer.from(anchor.utils.bytes.utf8.encode('user'))
+  const groupSeed = Buffer.from(anchor.utils.bytes.utf8.encode('groupchat'))
+  const inviteSeed = Buffer.from(anchor.utils.bytes.utf8.encode('invite'))
+  const membershipIndexSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('membership'),
+  )
+  const membershipPageSeed = Buffer.from(
+    anchor.utils.bytes.utf8.encode('membershippage'),
+  )
+  const rosterSeed = Buffer.from(anchor.utils.bytes.utf8.encode('roster'))
+  const encryptionKey =
+    'dhfskjdfhsdjkfhsdjkfhdsjkhdjkfdhfskjdfhsdjkfhsdjkfhdsjkhdjkfdfrt'
+
+  const sighash = (name: string) =>
+    Buffer.from(anchor.utils.sha256.hash(`global:${name}`), 'hex').slice(0, 8)
+
+  // Accounts for the tests.
+  const authority = anchor.web3.Keypair.generate()
+  const user = anchor.web3.Keypair.generate()
+  const otherUser = anchor.web3.Keypair.generate()
+  const sponsor = anchor.utils.publicKey.findProgramAddressSync(
+    [authority.publicKey.toBytes(), sponsorSeed],
+    program.programId,
+  )[0]
+  const vault = anchor.utils.publicKey.findProgramAddressSync(
+    [sponsor.toBytes(), vaultSeed],
+    program.programId,
+  )[0]
+  const quotaOf = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [sponsor.toBytes(), user.toBytes(), quotaSeed],
+      program.programId,
+    )[0]
+  const userAccountOf = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBytes(), userSeed],
+      usersProgram.programId,
+    )[0]
+  const pageBytes = (page: number) => {
+    const bytes = Buffer.alloc(4)
+    bytes.writeUInt32LE(page)
+    return bytes
+  }
+  const invitationOf = (
+    user: anchor.web3.PublicKey,
+    group: anchor.web3.PublicKey,
+  ) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBytes(), group.toBytes(), inviteSeed],
+      groupchatsProgram.programId,
+    )[0]
+  const membershipIndexOf = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), membershipIndexSeed],
+      groupchatsProgram.programId,
+    )[0]
+  const membershipPageOf = (user: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [user.toBuffer(), pageBytes(0), membershipPageSeed],
+      groupchatsProgram.programId,
+    )[0]
+  const rosterPageOf = (group: anchor.web3.PublicKey) =>
+    anchor.utils.publicKey.findProgramAddressSync(
+      [group.toBuffer(), pageBytes(0), rosterSeed],
+      groupchatsProgram.programId,
+    )[0]
+
+  // accounts every sponsored instruction passes on to `fund_rent`
+  const sponsoredAccounts = (user: anchor.web3.PublicKey) => ({
+    sponsor,
+    vault,
+    quota: quotaOf(user),
+    instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+    sponsorshipProgram: program.programId,
+    systemProgram: SystemProgram.programId,
+  })
+
+  const createSponsoredUser = async (user: anchor.web3.Keypair) => {
+    await usersProgram.rpc.createSponsored('Sponsored', '', '', {
+      accounts: {
+        user: userAccountOf(user.publicKey),
+        signer: user.publicKey,
+        ...sponsoredAccounts(user.publicKey),
+      },
+      signers: [user],
+    })
+  }
+
+  const remainingQuota = async (user: anchor.web3.PublicKey) =>
+    (await program.account.quota.fetch(quotaOf(user))).remaining.toNumber()
+
+  const rentOf = async (account: anchor.web3.PublicKey) =>
+    (await provider.connection.getAccountInfo(account)).lamports
+
+  it('Creates a sponsor', async () => {
+    await program.rpc.createSponsor(
+      [
+        {
+          programId: usersProgram.programId,
+          sighash: sighash('create_sponsored'),
+        },
+        {
+          programId: friendsProgram.programId,
+          sighash: sighash('make_request_sponsored'),
+        },
+        {
+          programId: groupchatsProgram.programId,
+          sighash: sighash('invite_sponsored'),
+        },
+      ],
+      {
+        accounts: {
+          sponsor,
+          vault,
+          authority: authority.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [authority],
+      },
+    )
+
+    const sponsorAccount = await program.account.sponsor.fetch(sponsor)
+    assert.ok(sponsorAccount.authority.equals(authority.publicKey))
+    assert.ok(sponsorAccount.payer.equals(provider.wallet.publicKey))
+    assert.equal(sponsorAccount.allowedInstructions.length, 3)
+  })
+
+  it('Deposits into the vault', async () => {
+    await program.rpc.deposit(new anchor.BN(1000000000), {
+      accounts: {
+        sponsor,
+        vault,
+        depositor: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+    })
+
+    assert.equal(await provider.connection.getBalance(vault), 1000000000)
+  })
+
+  it('Sets user quotas', async () => {
+    for (const [quotaUser, lamports] of [
+      [user, 100000000],
+      [otherUser, 1000],
+    ] as [anchor.web3.Keypair, number][]) {
+      await program.rpc.setQuota(quotaUser.publicKey, new anchor.BN(lamports), {
+        accounts: {
+          quota: quotaOf(quotaUser.publicKey),
+          sponsor,
+          authority: authority.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [authority],
+      })
+    }
+
+    const quotaAccount = await program.account.quota.fetch(
+      quotaOf(user.publicKey),
+    )
+    assert.ok(quotaAccount.user.equals(user.publicKey))
+    assert.equal(quotaAccount.remaining.toNumber(), 100000000)
+  })
+
+  it('Cannot set quota without authority', async () => {
+    try {
+      await program.rpc.setQuota(user.publicKey, new anchor.BN(1), {
+        accounts: {
+          quota: quotaOf(user.publicKey),
+          sponsor,
+          authority: user.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [user],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'User cannot perform this action'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Funds the profile of a user without lamports', async () => {
+    await createSponsoredUser(user)
+
+    const userAccount = await usersProgram.account.user.fetch(
+      userAccountOf(user.publicKey),
+    )
+    assert.equal(userAccount.name, 'Sponsored')
+    assert.ok(userAccount.payer.equals(vault))
+    assert.equal(await provider.connection.getBalance(user.publicKey), 0)
+    assert.equal(
+      await remainingQuota(user.publicKey),
+      100000000 - (await rentOf(userAccountOf(user.publicKey))),
+    )
+  })
+
+  it('Sponsored profile cannot be created twice', async () => {
+    try {
+      await createSponsoredUser(user)
+      assert.ok(false)
+    } catch (err) {
+      assert.ok(err.logs.some((log) => log.includes('Account already exists')))
+    }
+  })
+
+  it('Cannot draw more than the quota', async () => {
+    try {
+      await createSponsoredUser(otherUser)
+      assert.ok(false)
+    } catch (err) {
+      assert.ok(err.logs.some((log) => log.includes('Sponsor quota exceeded')))
+    }
+
+    assert.equal(
+      await provider.connection.getAccountInfo(
+        userAccountOf(otherUser.publicKey),
+      ),
+      null,
+    )
+  })
+
+  it('Rent cannot be funded outside a sponsored instruction', async () => {
+    const target = anchor.web3.Keypair.generate()
+    try {
+      await program.rpc.fundRent(new anchor.BN(100), {
+        accounts: {
+          sponsor,
+          vault,
+          quota: quotaOf(user.publicKey),
+          user: user.publicKey,
+          account: target.publicKey,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [user],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Instruction is not sponsored'
+      assert.equal(err.toString(), errMsg)
+    }
+  })
+
+  it('Funds a friend request of a user without lamports', async () => {
+    const [user1, user2] = [user.publicKey, otherUser.publicKey].sort((a, b) =>
+      Buffer.compare(b.toBuffer(), a.toBuffer()),
+    )
+    const request = anchor.utils.publicKey.findProgramAddressSync(
+      [user1.toBytes(), user2.toBytes()],
+      friendsProgram.programId,
+    )[0]
+    const quotaBefore = await remainingQuota(user.publicKey)
+
+    await friendsProgram.rpc.makeRequestSponsored(user1, user2, encryptionKey, {
+      accounts: {
+        request,
+        user: user.publicKey,
+        ...sponsoredAccounts(user.publicKey),
+      },
+      signers: [user],
+    })
+
+    const requestAccount = await friendsProgram.account.friendRequest.fetch(
+      request,
+    )
+    assert.ok(requestAccount.from.equals(user.publicKey))
+    assert.ok(requestAccount.payer.equals(vault))
+    assert.equal(
+      await remainingQuota(user.publicKey),
+      quotaBefore - (await rentOf(request)),
+    )
+  })
+
+  const sponsoredGroupHash = createHash('sha256')
+    .update('sponsoredgroup')
+    .update(user.publicKey.toBuffer())
+    .digest()
+  const sponsoredGroup = anchor.utils.publicKey.findProgramAddressSync(
+    [sponsoredGroupHash, groupSeed],
+    groupchatsProgram.programId,
+  )[0]
+
+  it('Funds an invitation sent by a user without lamports', async () => {
+    const group = sponsoredGroup
+    await groupchatsProgram.rpc.create(
+      sponsoredGroupHash,
+      'sponsoredgroup',
+      true,
+      'Sponsored group',
+      encryptionKey,
+      { textile: {} },
+      { private: {} },
+      0,
+      '',
+      [],
+      {
+        accounts: {
+          group,
+          invitation: invitationOf(user.publicKey, group),
+          membershipIndex: membershipIndexOf(user.publicKey),
+          membershipPage: membershipPageOf(user.publicKey),
+          rosterPage: rosterPageOf(group),
+          signer: user.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [user],
+      },
+    )
+    const quotaBefore = await remainingQuota(user.publicKey)
+
+    await groupchatsProgram.rpc.inviteSponsored(
+      otherUser.publicKey,
+      encryptionKey,
+      {
+        accounts: {
+          newInvitation: invitationOf(otherUser.publicKey, group),
+          group,
+          invitation: invitationOf(user.publicKey, group),
+          membershipIndex: membershipIndexOf(otherUser.publicKey),
+          membershipPage: membershipPageOf(otherUser.publicKey),
+          rosterPage: rosterPageOf(group),
+          signer: user.publicKey,
+          ...sponsoredAccounts(user.publicKey),
+        },
+        signers: [user],
+      },
+    )
+
+    const invitationAccount = await groupchatsProgram.account.invitation.fetch(
+      invitationOf(otherUser.publicKey, group),
+    )
+    const groupAccount = await groupchatsProgram.account.group.fetch(group)
+    assert.ok(invitationAccount.payer.equals(vault))
+    assert.ok(invitationAccount.recipient.equals(otherUser.publicKey))
+    assert.ok(groupAccount.members == 2)
+    // the roster page already existed, the other accounts were created
+    let funded = 0
+    for (const account of [
+      invitationOf(otherUser.publicKey, group),
+      membershipIndexOf(otherUser.publicKey),
+      membershipPageOf(otherUser.publicKey),
+    ]) {
+      funded += await rentOf(account)
+    }
+    assert.equal(await remainingQuota(user.publicKey), quotaBefore - funded)
+
+    const membershipIndexAccount =
+      await groupchatsProgram.account.membershipIndex.fetch(
+        membershipIndexOf(otherUser.publicKey),
+      )
+    const membershipPageAccount =
+      await groupchatsProgram.account.membershipPage.fetch(
+        membershipPageOf(otherUser.publicKey),
+      )
+    assert.ok(membershipIndexAccount.payer.equals(vault))
+    assert.ok(membershipPageAccount.payer.equals(vault))
+  })
+
+  it('Rent of a sponsored invitation goes back to the vault', async () => {
+    const vaultBefore = await provider.connection.getBalance(vault)
+    let refunded = 0
+    for (const account of [
+      invitationOf(otherUser.publicKey, sponsoredGroup),
+      membershipIndexOf(otherUser.publicKey),
+      membershipPageOf(otherUser.publicKey),
+    ]) {
+      refunded += await rentOf(account)
+    }
+
+    await groupchatsProgram.rpc.leave({
+      accounts: {
+        group: sponsoredGroup,
+        invitation: invitationOf(otherUser.publicKey, sponsoredGroup),
+        membershipIndex: membershipIndexOf(otherUser.publicKey),
+        membershipPage: membershipPageOf(otherUser.publicKey),
+        membershipLastPage: membershipPageOf(otherUser.publicKey),
+        rosterPage: rosterPageOf(sponsoredGroup),
+        rosterLastPage: rosterPageOf(sponsoredGroup),
+        signer: otherUser.publicKey,
+        invitationPayer: vault,
+      },
+      signers: [otherUser],
+    })
+    await groupchatsProgram.rpc.closeMembershipPage({
+      accounts: {
+        membershipPage: membershipPageOf(otherUser.publicKey),
+        payer: vault,
+      },
+    })
+    await groupchatsProgram.rpc.closeMembershipIndex({
+      accounts: {
+        membershipIndex: membershipIndexOf(otherUser.publicKey),
+        payer: vault,
+      },
+    })
+
+    assert.equal(
+      await provider.connection.getBalance(vault),
+      vaultBefore + refunded,
+    )
+  })
+
+  it('Withdraws from the vault', async () => {
+    await program.rpc.withdraw(new anchor.BN(500000000), {
+      accounts: {
+        sponsor,
+        vault,
+        authority: authority.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [authority],
+    })
+
+    assert.equal(
+      await provider.connection.getBalance(authority.publicKey),
+      500000000,
+    )
+  })
+
+  it('Closes a quota', async () => {
+    await program.rpc.closeQuota({
+      accounts: {
+        quota: quotaOf(otherUser.publicKey),
+        sponsor,
+        authority: authority.publicKey,
+        payer: provider.wallet.publicKey,
+      },
+      signers: [authority],
+    })
+
+    assert.equal(
+      await provider.connection.getAccountInfo(quotaOf(otherUser.publicKey)),
+      null,
+    )
+  })
+
+  it('Sponsor payer is transferred with the authority co-signing', async () => {
+    const newPayer = anchor.web3.Keypair.generate()
+    try {
+      await program.rpc.transferPayer({
+        accounts: {
+          sponsor,
+          authority: authority.publicKey,
+          payer: authority.publicKey,
+          newPayer: newPayer.publicKey,
+        },
+        signers: [authority],
+      })
+      assert.ok(false)
+    } catch (err) {
+      const errMsg = 'Account was not created by provided user'
+      assert.equal(err.toString(), errMsg)
+    }
+
+    await program.rpc.transferPayer({
+      accounts: {
+        sponsor,
+        authority: authority.publicKey,
+        payer: provider.wallet.publicKey,
+        newPayer: newPayer.publicKey,
+      },
+      signers: [authority],
+    })
+
+    const sponsorAccount = await program.account.sponsor.fetch(sponsor)
+    assert.ok(sponsorAccount.payer.equals(newPayer.publicKey))
+  })
+})
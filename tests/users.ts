@@ -31,6 +31,29 @@ describe('users', () => {
     [user.publicKey.toBytes(), userSeed],
     program.programId,
   )
+  // The profile only closes once its friend and membership indexes are empty
+  const friendIndex = anchor.utils.publicKey.findProgramAddressSync(
+    [
+      user.publicKey.toBytes(),
+      Buffer.from(anchor.utils.bytes.utf8.encode('friendindex')),
+    ],
+    anchor.workspace.Friends.programId,
+  )
+  const membershipIndex = anchor.utils.publicKey.findProgramAddressSync(
+    [
+      user.publicKey.toBytes(),
+      Buffer.from(anchor.utils.bytes.utf8.encode('membership')),
+    ],
+    anchor.workspace.Groupchats.programId,
+  )
+  // left behind by `close` so friends and groups can still drop the user
+  const closedProfile = anchor.utils.publicKey.findProgramAddressSync(
+    [
+      user.publicKey.toBytes(),
+      Buffer.from(anchor.utils.bytes.utf8.encode('closedprofile')),
+    ],
+    program.programId,
+  )
 
   it('Creates a new user account', async () => {
     // Airdropping tokens to a payer.
@@ -63,7 +86,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: impostor.publicKey,
-          payer: impostor.publicKey,
         },
         signers: [impostor],
       })
@@ -91,7 +113,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: impostor.publicKey,
-          payer: impostor.publicKey,
         },
         signers: [impostor],
       })
@@ -119,7 +140,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: impostor.publicKey,
-          payer: impostor.publicKey,
         },
         signers: [impostor],
       })
@@ -147,7 +167,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: impostor.publicKey,
-          payer: impostor.publicKey,
         },
         signers: [impostor],
       })
@@ -174,7 +193,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: impostor.publicKey,
-          payer: impostor.publicKey,
         },
         signers: [impostor],
       })
@@ -201,7 +219,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: impostor.publicKey,
-          payer: impostor.publicKey,
         },
         signers: [impostor],
       })
@@ -221,14 +238,13 @@ describe('users', () => {
     assert.ok(userAccountAfter.extra2 == extra2)
   })
 
-  it("Other payers can pay for the transaction in order to modify user's name", async () => {
+  it("Other payers cannot modify user's name without the user's signature", async () => {
       const newName = 'Jhon'
     try {
       await program.rpc.setName(newName, {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: otherPayer.publicKey,
         },
         signers: [otherPayer],
       })
@@ -245,16 +261,25 @@ describe('users', () => {
     assert.ok(userAccountAfter.extra2 == extra2)
   })
 
-  it("Other payers can pay for the transaction in order to modify user's name", async () => {
+  it("Other payers can pay the fees in order to modify user's name", async () => {
     const newName = 'Jhon'
-      await program.rpc.setName(newName, {
-        accounts: {
-          user: userAccount[0],
-          signer: user.publicKey,
-          payer: otherPayer.publicKey,
-        },
-        signers: [otherPayer, user],
-      })
+      await provider.connection.confirmTransaction(
+        await provider.connection.requestAirdrop(otherPayer.publicKey, 1000000000),
+        'confirmed',
+      )
+      const tx = new anchor.web3.Transaction().add(
+        program.instruction.setName(newName, {
+          accounts: {
+            user: userAccount[0],
+            signer: user.publicKey,
+          },
+        }),
+      )
+      tx.feePayer = otherPayer.publicKey
+      await anchor.web3.sendAndConfirmTransaction(provider.connection, tx, [
+        otherPayer,
+        user,
+      ])
 
     let userAccountAfter = await program.account.user.fetch(userAccount[0])
 
@@ -272,7 +297,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -296,7 +320,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -321,7 +344,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -346,7 +368,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -371,7 +392,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -398,7 +418,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -424,7 +443,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -450,7 +468,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -477,7 +494,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -503,7 +519,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -529,7 +544,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -556,7 +570,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -582,7 +595,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -608,7 +620,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -631,7 +642,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -655,7 +665,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -681,7 +690,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -704,7 +712,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -728,7 +735,6 @@ describe('users', () => {
         accounts: {
           user: userAccount[0],
           signer: user.publicKey,
-          payer: user.publicKey,
         },
         signers: [user],
       })
@@ -754,7 +760,6 @@ describe('users', () => {
       accounts: {
         user: userAccount[0],
         signer: user.publicKey,
-        payer: user.publicKey,
       },
       signers: [user],
     })
@@ -771,6 +776,119 @@ describe('users', () => {
     extra2 = newExtra2
   })
 
+  it('Impostor cannot transfer the payer', async () => {
+    try {
+      await program.rpc.transferPayer({
+        accounts: {
+          user: userAccount[0],
+          signer: user.publicKey,
+          payer: impostor.publicKey,
+          newPayer: impostor.publicKey,
+        },
+        signers: [user, impostor],
+      })
+      assert.ok(false)
+    } catch (error) {
+      const errMsg = 'Account was not created by provided user'
+      assert.equal(error.msg, errMsg)
+    }
+  })
+
+  it('Payer cannot be transferred without the user signature', async () => {
+    try {
+      await program.rpc.transferPayer({
+        accounts: {
+          user: userAccount[0],
+          signer: impostor.publicKey,
+          payer: user.publicKey,
+          newPayer: otherPayer.publicKey,
+        },
+        signers: [user, impostor],
+      })
+      assert.ok(false)
+    } catch (error) {
+      assert.equal(error.msg, 'A seeds constraint was violated')
+    }
+  })
+
+  it('Payer transfers the refund destination', async () => {
+    await program.rpc.transferPayer({
+      accounts: {
+        user: userAccount[0],
+        signer: user.publicKey,
+        payer: user.publicKey,
+        newPayer: otherPayer.publicKey,
+      },
+      signers: [user],
+    })
+
+    let userAccountAfter = await program.account.user.fetch(userAccount[0])
+    assert.ok(userAccountAfter.payer.equals(otherPayer.publicKey))
+
+    await program.rpc.transferPayer({
+      accounts: {
+        user: userAccount[0],
+        signer: user.publicKey,
+        payer: otherPayer.publicKey,
+        newPayer: user.publicKey,
+      },
+      signers: [user, otherPayer],
+    })
+
+    userAccountAfter = await program.account.user.fetch(userAccount[0])
+    assert.ok(userAccountAfter.payer.equals(user.publicKey))
+  })
+
+  it('User cannot close account before deactivating it', async () => {
+    try {
+      await program.rpc.close({
+        accounts: {
+          user: userAccount[0],
+          signer: user.publicKey,
+          payer: user.publicKey,
+          friendIndex: friendIndex[0],
+          membershipIndex: membershipIndex[0],
+          closedProfile: closedProfile[0],
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [user],
+      })
+      assert.ok(false)
+    } catch (error) {
+      const errMsg = 'User profile must be deactivated first'
+      assert.equal(error.msg, errMsg)
+    }
+  })
+
+  it('User deactivates account', async () => {
+    await program.rpc.deactivate({
+      accounts: {
+        user: userAccount[0],
+        signer: user.publicKey,
+      },
+      signers: [user],
+    })
+
+    const userAccountAfter = await program.account.user.fetch(userAccount[0])
+    assert.ok(userAccountAfter.closing)
+  })
+
+  it('User cannot modify a deactivated account', async () => {
+    try {
+      await program.rpc.setName('Deactivated', {
+        accounts: {
+          user: userAccount[0],
+          signer: user.publicKey,
+        },
+        signers: [user],
+      })
+      assert.ok(false)
+    } catch (error) {
+      const errMsg = 'User profile is being closed'
+      assert.equal(error.msg, errMsg)
+    }
+  })
+
   it('Impostor cannot close account', async () => {
 
     try{  
@@ -779,6 +897,10 @@ describe('users', () => {
           user: userAccount[0],
           signer: impostor.publicKey,
           payer: user.publicKey,
+          friendIndex: friendIndex[0],
+          membershipIndex: membershipIndex[0],
+          closedProfile: closedProfile[0],
+          systemProgram: SystemProgram.programId,
         },
         signers: [impostor],
       })
@@ -798,6 +920,10 @@ describe('users', () => {
           user: userAccount[0],
           signer: user.publicKey,
           payer: impostor.publicKey,
+          friendIndex: friendIndex[0],
+          membershipIndex: membershipIndex[0],
+          closedProfile: closedProfile[0],
+          systemProgram: SystemProgram.programId,
         },
         signers: [user],
       })
@@ -816,10 +942,72 @@ describe('users', () => {
         user: userAccount[0],
         signer: user.publicKey,
         payer: user.publicKey,
+        friendIndex: friendIndex[0],
+        membershipIndex: membershipIndex[0],
+        closedProfile: closedProfile[0],
+        systemProgram: SystemProgram.programId,
       },
       signers: [user],
     })
     console.log(await provider.connection.getBalance(user.publicKey))
-    
+
+    const closedProfileAccount = await program.account.closedProfile.fetch(
+      closedProfile[0],
+    )
+    assert.ok(closedProfileAccount.signer.equals(user.publicKey))
+    const userAccountAfter = await program.account.user.fetchNullable(
+      userAccount[0],
+    )
+    assert.ok(userAccountAfter == null)
+  })
+
+  // profile preloaded from tests/fixtures, written before `closing` existed
+  const legacyUser = anchor.web3.Keypair.fromSeed(Buffer.alloc(32, 9))
+  const legacyProfile = anchor.utils.publicKey.findProgramAddressSync(
+    [legacyUser.publicKey.toBytes(), userSeed],
+    program.programId,
+  )[0]
+
+  const migrateProfile = async () => {
+    await program.rpc.migrateProfile({
+      accounts: {
+        user: legacyProfile,
+        payer: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+    })
+  }
+
+  it('Anyone grows a legacy profile to the current layout', async () => {
+    await migrateProfile()
+
+    const info = await provider.connection.getAccountInfo(legacyProfile)
+    const legacyProfileAccount = await program.account.user.fetch(
+      legacyProfile,
+    )
+    assert.equal(info.data.length, 513)
+    assert.equal(legacyProfileAccount.name, 'n'.repeat(32))
+    assert.equal(legacyProfileAccount.extra2, '2'.repeat(64))
+    assert.ok(!legacyProfileAccount.closing)
+
+    await program.rpc.deactivate({
+      accounts: {
+        user: legacyProfile,
+        signer: legacyUser.publicKey,
+      },
+      signers: [legacyUser],
+    })
+    const deactivated = await program.account.user.fetch(legacyProfile)
+    assert.ok(deactivated.closing)
+  })
+
+  it('Profile cannot be migrated twice', async () => {
+    try {
+      await migrateProfile()
+      assert.ok(false)
+    } catch (error) {
+      const errMsg = 'Account is not a profile in the legacy layout'
+      assert.equal(error.msg, errMsg)
+    }
   })
 })